@@ -0,0 +1,305 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+};
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tokio::sync::RwLock;
+
+use crate::live_event::LiveEvent;
+
+/// 滚动窗口弹幕分析：只保留最近 ANALYTICS_WINDOW_MS 内的弹幕，查询时现算。
+/// 用于判断刷屏高峰（是否暂停字幕层），不做持久化。
+/// 统计的是过滤前的原始弹幕（刷屏正是会被去重/限速挡掉的那部分），另外单独给出实际转发的速率。
+const ANALYTICS_WINDOW_MS: u64 = 5 * 60 * 1000;
+// 刷屏判定：最近 BURST_WINDOW_MS 的速率超过窗口平均速率 BURST_RATIO 倍，且不低于 BURST_MIN_PER_MINUTE
+const BURST_WINDOW_MS: u64 = 10 * 1000;
+const BURST_RATIO: f64 = 3.0;
+const BURST_MIN_PER_MINUTE: f64 = 60.0;
+const TOP_WORDS: usize = 10;
+
+// 简单情绪词表：只用于粗分桶，命中任一即归类（同时命中按先正后负）
+const POSITIVE_WORDS: &[&str] = &[
+    "哈哈", "好听", "好看", "可爱", "厉害", "牛", "666", "棒", "喜欢", "爱了", "awsl", "yyds", "lol", "nice", "gg", "pog",
+];
+const NEGATIVE_WORDS: &[&str] = &[
+    "难听", "难看", "无聊", "垃圾", "退钱", "卡了", "好卡", "辣鸡", "差评", "烂", "sb", "wtf", "boring",
+];
+
+/// 一条弹幕的统计样本；在过滤前取（规则可能改写文本），过滤后连同是否转发一起记录
+pub struct Sample {
+    time: u64,
+    user: String,
+    text: String,
+    forwarded: bool,
+}
+
+static SAMPLES: Lazy<Arc<RwLock<VecDeque<Sample>>>> = Lazy::new(|| Arc::new(RwLock::new(VecDeque::new())));
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WordCount {
+    pub word: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SentimentBuckets {
+    pub positive: u64,
+    pub negative: u64,
+    pub neutral: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyticsSnapshot {
+    pub window_secs: u64,
+    // 最近 60 秒的弹幕数
+    pub messages_per_minute: u64,
+    // 窗口内平均每分钟弹幕数
+    pub average_per_minute: f64,
+    // 最近 10 秒折算的每分钟速率
+    pub current_rate_per_minute: f64,
+    // 最近 60 秒实际转发到字幕层的弹幕数（其余被过滤/去重/限速）
+    pub forwarded_per_minute: u64,
+    // 最近 10 秒折算的每分钟转发速率
+    pub forwarded_rate_per_minute: f64,
+    pub unique_chatters: usize,
+    // 按分钟分桶（旧 -> 新），最后一个为当前分钟
+    pub per_minute: Vec<u64>,
+    pub top_words: Vec<WordCount>,
+    pub sentiment: SentimentBuckets,
+    // 正在刷屏：当前速率显著高于窗口平均
+    pub burst: bool,
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+fn prune(samples: &mut VecDeque<Sample>, now: u64) {
+    while samples.front().is_some_and(|s| s.time + ANALYTICS_WINDOW_MS < now) {
+        samples.pop_front();
+    }
+}
+
+/// 过滤前取样（其它事件不参与统计）
+pub fn sample_of(event: &LiveEvent) -> Option<Sample> {
+    let LiveEvent::Danmu(danmu) = event else {
+        return None;
+    };
+    let common = &danmu.common;
+    let user = match common.user.uid.filter(|uid| *uid != 0) {
+        Some(uid) => format!("uid:{uid}"),
+        None => format!("name:{}", common.user.name),
+    };
+    Some(Sample {
+        time: now_ms(),
+        user,
+        text: common.text.clone(),
+        forwarded: false,
+    })
+}
+
+/// 记录一条弹幕及其是否被转发
+pub async fn record(mut sample: Sample, forwarded: bool) {
+    sample.forwarded = forwarded;
+    let mut samples = SAMPLES.write().await;
+    prune(&mut samples, sample.time);
+    samples.push_back(sample);
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x4E00..=0x9FFF | 0x3400..=0x4DBF | 0x3040..=0x30FF | 0xAC00..=0xD7AF | 0xF900..=0xFAFF)
+}
+
+/// 分词：拉丁字母/数字按单词切分（小写，至少 2 个字符）；中日韩文字没有空格，按字符二元组（bigram）切分。
+/// 同一条弹幕里重复的词只计一次，避免“哈哈哈哈哈哈”一条刷满榜单。
+fn tokenize(text: &str) -> HashSet<String> {
+    let mut tokens = HashSet::new();
+    let mut word = String::new();
+    let mut cjk_run: Vec<char> = Vec::new();
+
+    let flush_word = |word: &mut String, tokens: &mut HashSet<String>| {
+        if word.chars().count() >= 2 {
+            tokens.insert(word.to_lowercase());
+        }
+        word.clear();
+    };
+    let flush_cjk = |run: &mut Vec<char>, tokens: &mut HashSet<String>| {
+        for pair in run.windows(2) {
+            tokens.insert(pair.iter().collect());
+        }
+        run.clear();
+    };
+
+    for c in text.chars() {
+        if is_cjk(c) {
+            flush_word(&mut word, &mut tokens);
+            cjk_run.push(c);
+        } else if c.is_alphanumeric() {
+            flush_cjk(&mut cjk_run, &mut tokens);
+            word.push(c);
+        } else {
+            flush_word(&mut word, &mut tokens);
+            flush_cjk(&mut cjk_run, &mut tokens);
+        }
+    }
+    flush_word(&mut word, &mut tokens);
+    flush_cjk(&mut cjk_run, &mut tokens);
+    tokens
+}
+
+fn sentiment_of(text: &str) -> i8 {
+    let lower = text.to_lowercase();
+    if POSITIVE_WORDS.iter().any(|w| lower.contains(w)) {
+        1
+    } else if NEGATIVE_WORDS.iter().any(|w| lower.contains(w)) {
+        -1
+    } else {
+        0
+    }
+}
+
+pub async fn snapshot() -> AnalyticsSnapshot {
+    let now = now_ms();
+    let mut samples = SAMPLES.write().await;
+    prune(&mut samples, now);
+    compute(&samples, now)
+}
+
+fn compute(samples: &VecDeque<Sample>, now: u64) -> AnalyticsSnapshot {
+    let minutes = ANALYTICS_WINDOW_MS / 60_000;
+    let mut per_minute = vec![0u64; minutes as usize];
+    let mut users = HashSet::new();
+    let mut words: HashMap<String, u64> = HashMap::new();
+    let mut sentiment = SentimentBuckets::default();
+    let mut last_minute = 0;
+    let mut last_burst_window = 0;
+    let mut forwarded_last_minute = 0;
+    let mut forwarded_burst_window = 0;
+
+    for s in samples.iter() {
+        let age = now.saturating_sub(s.time);
+        let bucket = (age / 60_000).min(minutes - 1) as usize;
+        per_minute[minutes as usize - 1 - bucket] += 1;
+        if age < 60_000 {
+            last_minute += 1;
+            forwarded_last_minute += u64::from(s.forwarded);
+        }
+        if age < BURST_WINDOW_MS {
+            last_burst_window += 1;
+            forwarded_burst_window += u64::from(s.forwarded);
+        }
+        users.insert(s.user.as_str());
+        for token in tokenize(&s.text) {
+            *words.entry(token).or_default() += 1;
+        }
+        match sentiment_of(&s.text) {
+            1 => sentiment.positive += 1,
+            -1 => sentiment.negative += 1,
+            _ => sentiment.neutral += 1,
+        }
+    }
+
+    // 窗口未满（刚开始统计）时按实际覆盖的时长算平均
+    let covered_ms = samples
+        .front()
+        .map(|s| now.saturating_sub(s.time).max(60_000))
+        .unwrap_or(ANALYTICS_WINDOW_MS)
+        .min(ANALYTICS_WINDOW_MS);
+    let average_per_minute = samples.len() as f64 * 60_000.0 / covered_ms as f64;
+    let current_rate_per_minute = last_burst_window as f64 * 60_000.0 / BURST_WINDOW_MS as f64;
+    let forwarded_rate_per_minute = forwarded_burst_window as f64 * 60_000.0 / BURST_WINDOW_MS as f64;
+    let burst = current_rate_per_minute >= BURST_MIN_PER_MINUTE
+        && current_rate_per_minute >= average_per_minute * BURST_RATIO;
+
+    let mut top_words = words
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(word, count)| WordCount { word, count })
+        .collect::<Vec<_>>();
+    top_words.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.word.cmp(&b.word)));
+    top_words.truncate(TOP_WORDS);
+
+    AnalyticsSnapshot {
+        window_secs: ANALYTICS_WINDOW_MS / 1000,
+        messages_per_minute: last_minute,
+        average_per_minute,
+        current_rate_per_minute,
+        forwarded_per_minute: forwarded_last_minute,
+        forwarded_rate_per_minute,
+        unique_chatters: users.len(),
+        per_minute,
+        top_words,
+        sentiment,
+        burst,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000_000;
+
+    fn sample(age_ms: u64, user: &str, text: &str, forwarded: bool) -> Sample {
+        Sample {
+            time: NOW - age_ms,
+            user: user.to_string(),
+            text: text.to_string(),
+            forwarded,
+        }
+    }
+
+    // 前 4 分钟每 5 秒一条，作为平常的速率
+    fn steady() -> VecDeque<Sample> {
+        (3..=48).rev().map(|i| sample(i * 5_000, "uid:1", "晚上好", true)).collect()
+    }
+
+    #[test]
+    fn detects_burst_above_window_average() {
+        let mut samples = steady();
+        // 最近 10 秒 20 条，只有 5 条转发（其余被去重挡掉）
+        for i in 0..20 {
+            samples.push_back(sample(9_500 - i * 500, &format!("uid:{}", 100 + i), "哈哈哈哈", i < 5));
+        }
+        let snap = compute(&samples, NOW);
+        assert!(snap.burst);
+        assert_eq!(snap.current_rate_per_minute, 120.0);
+        assert_eq!(snap.forwarded_rate_per_minute, 30.0);
+        assert_eq!(snap.unique_chatters, 21);
+        assert_eq!(snap.per_minute.len(), 5);
+        assert_eq!(snap.per_minute.iter().sum::<u64>(), 66);
+        // 同一条里重复的词只计一次
+        assert!(snap.top_words.iter().any(|w| w.word == "哈哈" && w.count == 20));
+        assert_eq!(snap.sentiment.positive, 20);
+    }
+
+    #[test]
+    fn steady_or_small_spikes_are_not_bursts() {
+        let snap = compute(&steady(), NOW);
+        assert!(!snap.burst);
+
+        // 平时很安静时的小高峰：倍数够但低于每分钟下限
+        let mut samples = steady();
+        for i in 0..5 {
+            samples.push_back(sample(i * 1_000, "uid:2", "来了", true));
+        }
+        let snap = compute(&samples, NOW);
+        assert_eq!(snap.current_rate_per_minute, 30.0);
+        assert!(!snap.burst);
+
+        // 一直很高的速率也不算刷屏
+        let busy = (0..600).map(|i| sample(i * 500, "uid:3", "666", true)).collect::<VecDeque<_>>();
+        let snap = compute(&busy, NOW);
+        assert!(snap.current_rate_per_minute >= BURST_MIN_PER_MINUTE);
+        assert!(!snap.burst);
+    }
+}
@@ -1,4 +1,4 @@
-use std::{io::Read, sync::Arc, time::{Duration, SystemTime, UNIX_EPOCH}};
+use std::{collections::HashMap, io::Read, sync::Arc, time::{Duration, SystemTime, UNIX_EPOCH}};
 
 use brotli::Decompressor; // 新增
 use flate2::read::ZlibDecoder;
@@ -10,15 +10,35 @@ use tokio_tungstenite::{
     connect_async,
     tungstenite::{self, Message},
 };
-use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
 
 use tauri::{AppHandle, Emitter};
 
+use crate::error::AppError;
+use crate::dedup::{DedupConfig, Deduper};
+use crate::filter_rules::{FilterRule, RuleEffects, RuleSet};
+use crate::keyword_filter::KeywordFilter;
+use crate::packet_log::PacketLogWriter;
+use crate::live_source::{run_pipeline, LiveSource, PipelineStats, SourceContext};
+use crate::live_event::{
+    DanmuEvent, EventCommon, GiftEvent, GuardBuyEvent, GuardLevel, InteractEvent, LiveEvent,
+    LiveStatusEvent, SuperChatDeleteEvent, SuperChatEvent, WatchedEvent, super_chat_event_id,
+};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DanmuFilterConfig {
     pub blacklist_enabled: bool,
     pub keyword_blacklist: Vec<String>,
+    // 正则黑名单（不区分大小写），命中任意一条则过滤
+    #[serde(default)]
+    pub regex_blacklist: Vec<String>,
+    // 匹配前先归一化：NFKC、全角转半角、繁转简，去掉空白与标点
+    #[serde(default = "default_true")]
+    pub normalize_text: bool,
+    // 拼音匹配（“傻逼”可命中“sb”“傻b”及同音的“沙比”）
+    #[serde(default)]
+    pub pinyin_match: bool,
     pub min_len: Option<u32>,
     pub max_len: Option<u32>,
     // 仅显示佩戴本房间粉丝牌的弹幕（media_ruid 与主播 uid 匹配）
@@ -27,6 +47,22 @@ pub struct DanmuFilterConfig {
     pub only_streamer: bool,
     // 屏蔽主播/本人弹幕
     pub hide_streamer: bool,
+    // 用户黑名单：uid 或用户名（完全匹配）命中则过滤
+    #[serde(default)]
+    pub uid_blacklist: Vec<u64>,
+    #[serde(default)]
+    pub user_blacklist: Vec<String>,
+    // 用户白名单：命中则跳过其它所有过滤条件（房管、固定嘉宾等）
+    #[serde(default)]
+    pub uid_whitelist: Vec<u64>,
+    #[serde(default)]
+    pub user_whitelist: Vec<String>,
+    // 重复/刷屏抑制
+    #[serde(default)]
+    pub dedup: DedupConfig,
+    // 有序规则列表：对所有事件生效，先于以上固定条件执行
+    #[serde(default)]
+    pub rules: Vec<FilterRule>,
 }
 
 impl Default for DanmuFilterConfig {
@@ -34,103 +70,332 @@ impl Default for DanmuFilterConfig {
         Self {
             blacklist_enabled: false,
             keyword_blacklist: Vec::new(),
+            regex_blacklist: Vec::new(),
+            normalize_text: true,
+            pinyin_match: false,
             min_len: None,
             max_len: None,
             only_fans_medal: false,
             only_streamer: false,
             hide_streamer: false,
+            uid_blacklist: Vec::new(),
+            user_blacklist: Vec::new(),
+            uid_whitelist: Vec::new(),
+            user_whitelist: Vec::new(),
+            dedup: DedupConfig::default(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl DanmuFilterConfig {
+    fn user_in(uids: &[u64], names: &[String], uid: Option<u64>, name: &str) -> bool {
+        uid.is_some_and(|uid| uid != 0 && uids.contains(&uid))
+            || (!name.is_empty() && names.iter().any(|n| n.trim() == name))
+    }
+}
+
+/// 弹幕被丢弃的原因（计入 Stats.dropped）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DropReason {
+    // 关键词/长度/粉丝牌/主播等内容过滤
+    Filtered,
+    Muted,
+    Blacklisted,
+    // 任何人短时间内发送相同内容
+    Duplicate,
+    // 同一用户重复相同内容
+    UserRepeat,
+    // 与最近弹幕高度相似
+    Similar,
+    // 超过单用户限速
+    RateLimited,
+    // 命中 drop 规则
+    Rule,
+}
+
+/// 一条事件经过管线的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Dispatched {
+    Forwarded,
+    Dropped(DropReason),
+    // 互动事件开关关闭
+    Ignored,
+}
+
+/// 一次过滤判定：丢弃原因与具体说明（ws_debug 下打印，dry-run 时返回给调用方）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterDrop {
+    pub reason: DropReason,
+    pub detail: String,
+}
+
+impl FilterDrop {
+    fn new(reason: DropReason, detail: String) -> Self {
+        Self { reason, detail }
+    }
+}
+
+/// 临时禁言：到期前该 uid 的弹幕不再转发（只影响字幕层，不会在直播间禁言）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MutedUser {
+    pub uid: u64,
+    #[serde(default)]
+    pub user: Option<String>,
+    // 到期时间（Unix 毫秒）
+    pub until: u64,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// 互动类事件的转发开关（默认全部关闭，避免进房等高频事件刷屏）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InteractEventConfig {
+    // 进入直播间（INTERACT_WORD msg_type=1 / ENTRY_EFFECT）
+    #[serde(default)]
+    pub enter: bool,
+    // 关注（INTERACT_WORD msg_type=2/4/5）
+    #[serde(default)]
+    pub follow: bool,
+    // 分享（INTERACT_WORD msg_type=3）
+    #[serde(default)]
+    pub share: bool,
+    // 点赞（LIKE_INFO_V3_CLICK）
+    #[serde(default)]
+    pub like: bool,
+    // 看过人数变化（WATCHED_CHANGE）
+    #[serde(default)]
+    pub watched: bool,
+}
+
+impl InteractEventConfig {
+    fn allows(&self, event: &LiveEvent) -> bool {
+        match event {
+            LiveEvent::Enter(_) => self.enter,
+            LiveEvent::Follow(_) => self.follow,
+            LiveEvent::Share(_) => self.share,
+            LiveEvent::Like(_) => self.like,
+            LiveEvent::Watched(_) => self.watched,
+            _ => true,
         }
     }
 }
 
 // 0 = follow env, 1 = force false, 2 = force true
 static WS_DEBUG_OVERRIDE: AtomicU8 = AtomicU8::new(0);
-static DANMU_FILTER: Lazy<Arc<RwLock<DanmuFilterConfig>>> = Lazy::new(|| Arc::new(RwLock::new(DanmuFilterConfig::default())));
+// 当前生效的过滤配置（连同编译好的关键词/正则与规则，避免每条弹幕重新编译）
+static DANMU_FILTER: Lazy<Arc<RwLock<Arc<ActiveFilter>>>> = Lazy::new(|| Arc::new(RwLock::new(Arc::new(ActiveFilter::default()))));
+// 临时禁言列表（uid -> 禁言信息），持久化在设置文件的 mutedUsers 中
+static MUTED_USERS: Lazy<Arc<RwLock<HashMap<u64, MutedUser>>>> = Lazy::new(|| Arc::new(RwLock::new(HashMap::new())));
+static INTERACT_EVENTS: Lazy<Arc<RwLock<InteractEventConfig>>> = Lazy::new(|| Arc::new(RwLock::new(InteractEventConfig::default())));
+// 心跳看门狗：超过该秒数没有收到任何服务端数据（含 OP_HEARTBEAT_REPLY）则判定连接已僵死；0 表示关闭
+static HEARTBEAT_TIMEOUT_SECS: AtomicU64 = AtomicU64::new(60);
 
 pub async fn set_ws_debug_enabled(enabled: bool) {
     WS_DEBUG_OVERRIDE.store(if enabled { 2 } else { 1 }, Ordering::Relaxed);
 }
 
-pub async fn set_danmu_filter_config(cfg: DanmuFilterConfig) {
-    *DANMU_FILTER.write().await = cfg;
+/// 编译好的一整套过滤配置；生效配置与 dry-run 的候选配置共用
+#[derive(Default)]
+pub struct ActiveFilter {
+    cfg: DanmuFilterConfig,
+    keywords: KeywordFilter,
+    rules: RuleSet,
+}
+
+impl ActiveFilter {
+    pub fn compile(cfg: DanmuFilterConfig) -> Result<Self, AppError> {
+        let keywords = KeywordFilter::compile(
+            &cfg.keyword_blacklist,
+            &cfg.regex_blacklist,
+            cfg.normalize_text,
+            cfg.pinyin_match,
+        )?;
+        let rules = RuleSet::compile(&cfg.rules)?;
+        Ok(Self { cfg, keywords, rules })
+    }
+}
+
+/// 正则无效时返回错误且不修改当前配置
+pub async fn set_danmu_filter_config(cfg: DanmuFilterConfig) -> Result<(), AppError> {
+    let filter = ActiveFilter::compile(cfg)?;
+    *DANMU_FILTER.write().await = Arc::new(filter);
+    Ok(())
+}
+
+/// 当前生效的过滤配置
+pub async fn danmu_filter_config() -> DanmuFilterConfig {
+    DANMU_FILTER.read().await.cfg.clone()
 }
 
 fn now_ms() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
-        .unwrap()
+        .unwrap_or_default()
         .as_millis() as u64
 }
 
-fn now_hms() -> String {
-    // 与 sse_server.rs 的默认 timestamp 格式保持一致
-    chrono::Local::now().format("%H:%M:%S").to_string()
+/// 用设置文件里的禁言列表初始化（已到期的直接丢弃）
+pub async fn set_muted_users(list: Vec<MutedUser>) {
+    let now = now_ms();
+    *MUTED_USERS.write().await = list
+        .into_iter()
+        .filter(|m| m.until > now)
+        .map(|m| (m.uid, m))
+        .collect();
 }
 
-fn hms_from_unix_ms(ms: u64) -> String {
-    use chrono::TimeZone;
+/// 禁言 uid 若干分钟；重复禁言以最新一次为准
+pub async fn mute_user(uid: u64, minutes: u64, user: Option<String>, reason: Option<String>) -> Result<MutedUser, AppError> {
+    if uid == 0 {
+        return Err(AppError::InvalidArgument("uid 不能为空".to_string()));
+    }
+    if minutes == 0 {
+        return Err(AppError::InvalidArgument("禁言时长必须大于 0 分钟".to_string()));
+    }
+    let muted = MutedUser {
+        uid,
+        user: user.filter(|s| !s.trim().is_empty()),
+        until: now_ms().saturating_add(minutes.saturating_mul(60_000)),
+        reason: reason.filter(|s| !s.trim().is_empty()),
+    };
+    MUTED_USERS.write().await.insert(uid, muted.clone());
+    println!("[Filter] 禁言 uid={} {} 分钟", uid, minutes);
+    Ok(muted)
+}
 
-    // B 站 DANMU_MSG 的 timestamp 为 Unix 毫秒时间戳
-    chrono::Local
-        .timestamp_millis_opt(ms as i64)
-        .single()
-        .map(|dt| dt.format("%H:%M:%S").to_string())
-        .unwrap_or_else(now_hms)
+/// 解除禁言，返回是否存在该禁言
+pub async fn unmute_user(uid: u64) -> bool {
+    MUTED_USERS.write().await.remove(&uid).is_some()
 }
 
-fn rgb_decimal_to_hex(v: u64) -> String {
-    // B 站 DANMU_MSG 的颜色字段通常是十进制 RGB
-    format!("#{:06x}", (v & 0x00ff_ffff))
+/// 当前生效的禁言（按到期时间排序），顺带清理已到期的
+pub async fn list_muted_users() -> Vec<MutedUser> {
+    let now = now_ms();
+    let mut muted = MUTED_USERS.write().await;
+    muted.retain(|_, m| m.until > now);
+    let mut list = muted.values().cloned().collect::<Vec<_>>();
+    list.sort_by_key(|m| m.until);
+    list
 }
 
-fn make_sse_event(
-    msg_type: &'static str,
-    text: String,
-    user: Option<String>,
-    color: Option<String>,
-    size: Option<u32>,
-    face_url: Option<String>,
-    media_ruid: Option<u32>,
-    time_ms: Option<u64>,
-) -> serde_json::Value {
-    let timestamp = time_ms.unwrap_or_else(now_ms);
-    let timestamp_text = hms_from_unix_ms(timestamp);
-    serde_json::json!({
-        "type": msg_type,
-        "text": text,
-        "user": user.unwrap_or_else(|| "匿名用户".to_string()),
-        "color": color.unwrap_or_else(|| "#ffffff".to_string()),
-        "size": size.unwrap_or(32),
-        // 兼容字段：同样使用 Unix 毫秒时间戳
-        "time": now_ms(),
-        // 对齐 blivedm/web.py: timestamp 是 Unix 毫秒时间戳
-        "timestamp": timestamp,
-        // 仅用于展示的人类可读时间（不要当作 timestamp 使用）
-        "timestampText": timestamp_text,
-        "face_url": face_url,
-        "media_ruid": media_ruid,
-        // 样式/过滤辅助字段（缺省由 forward_to_sse 兜底补齐）
-        "hasOwnMedal": false,
-        "isModerator": false,
-        "isStreamer": false,
-        // 舰队等级：0非舰队, 1总督, 2提督, 3舰长（blivedm/web.py: privilege_type）
-        "guardLevel": 0,
-    })
+async fn is_muted(uid: Option<u64>) -> bool {
+    let Some(uid) = uid.filter(|uid| *uid != 0) else {
+        return false;
+    };
+    MUTED_USERS.read().await.get(&uid).is_some_and(|m| m.until > now_ms())
+}
+
+pub async fn set_interact_event_config(cfg: InteractEventConfig) {
+    *INTERACT_EVENTS.write().await = cfg;
+}
+
+pub async fn set_heartbeat_timeout_secs(secs: u64) {
+    HEARTBEAT_TIMEOUT_SECS.store(secs, Ordering::Relaxed);
+}
+
+pub(crate) fn heartbeat_timeout_secs() -> u64 {
+    HEARTBEAT_TIMEOUT_SECS.load(Ordering::Relaxed)
+}
+
+/* ---------------- 原始包录制 ---------------- */
+
+/// 录制中的会话：在 handle_packet 之前把收到的二进制帧原样写入 packet_log 文件，用于离线复现解析问题
+struct PacketRecording {
+    // 只录制该连接；None 表示录制所有连接
+    connection_id: Option<String>,
+    path: std::path::PathBuf,
+    writer: PacketLogWriter,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PacketRecordingInfo {
+    pub path: String,
+    pub connection_id: Option<String>,
+    pub frames: u64,
+}
+
+impl PacketRecording {
+    fn info(&self) -> PacketRecordingInfo {
+        PacketRecordingInfo {
+            path: self.path.to_string_lossy().to_string(),
+            connection_id: self.connection_id.clone(),
+            frames: self.writer.frames(),
+        }
+    }
+}
+
+static PACKET_RECORDING: Lazy<Arc<RwLock<Option<PacketRecording>>>> = Lazy::new(|| Arc::new(RwLock::new(None)));
+
+pub async fn start_packet_recording(
+    path: std::path::PathBuf,
+    connection_id: Option<String>,
+) -> Result<PacketRecordingInfo, AppError> {
+    let mut rec = PACKET_RECORDING.write().await;
+    if let Some(current) = rec.as_ref() {
+        return Err(AppError::InvalidArgument(format!(
+            "已在录制中: {}，请先停止",
+            current.path.display()
+        )));
+    }
+    let writer = PacketLogWriter::create(&path)
+        .map_err(|e| AppError::Io(format!("创建录制文件失败: {e}")))?;
+    let recording = PacketRecording {
+        connection_id: connection_id.filter(|s| !s.trim().is_empty()),
+        path,
+        writer,
+    };
+    let info = recording.info();
+    println!("[Record] 开始录制原始包: {}", info.path);
+    *rec = Some(recording);
+    Ok(info)
+}
+
+/// 停止录制；未在录制时返回 None
+pub async fn stop_packet_recording() -> Option<PacketRecordingInfo> {
+    let recording = PACKET_RECORDING.write().await.take()?;
+    let info = recording.info();
+    println!("[Record] 停止录制: {}（{} 帧）", info.path, info.frames);
+    Some(info)
+}
+
+pub async fn packet_recording_status() -> Option<PacketRecordingInfo> {
+    PACKET_RECORDING.read().await.as_ref().map(|r| r.info())
+}
+
+async fn record_packet(connection_id: &str, frame: &[u8]) {
+    if PACKET_RECORDING.read().await.is_none() {
+        return;
+    }
+    let mut rec = PACKET_RECORDING.write().await;
+    let Some(recording) = rec.as_mut() else {
+        return;
+    };
+    if recording.connection_id.as_deref().is_some_and(|id| id != connection_id) {
+        return;
+    }
+    if let Err(e) = recording.writer.write_frame(now_ms(), frame) {
+        // 磁盘写满等情况：停止录制，不影响正常转发
+        eprintln!("[Record] 写入失败，停止录制 {}: {e}", recording.path.display());
+        *rec = None;
+    }
 }
 
-fn make_sse_danmu(
-    text: String,
-    user: Option<String>,
-    color: Option<String>,
-    size: Option<u32>,
-    face_url: Option<String>,
-    media_ruid: Option<u32>,
-    time_ms: Option<u64>,
-) -> serde_json::Value {
-    make_sse_event("danmu", text, user, color, size, face_url, media_ruid, time_ms)
+fn rgb_decimal_to_hex(v: u64) -> String {
+    // B 站 DANMU_MSG 的颜色字段通常是十进制 RGB
+    format!("#{:06x}", (v & 0x00ff_ffff))
 }
 
-fn parse_danmu_msg(root: &serde_json::Value) -> Option<serde_json::Value> {
+fn parse_danmu_msg(root: &serde_json::Value) -> Option<LiveEvent> {
     // 典型结构：{"cmd":"DANMU_MSG", "info": [meta, text, user, ...]}
     
     let text = root.pointer("/info/1")?.as_str()?.to_string();
@@ -145,108 +410,537 @@ fn parse_danmu_msg(root: &serde_json::Value) -> Option<serde_json::Value> {
         .and_then(|v| v.as_str())
         .map(|s| s.to_string());
 
+    let mut common = EventCommon::new(text, user, time_ms);
+
     // 用户 uid：用于识别“主播/本人弹幕”（比 face_url 更稳定）
-    let uid = root
+    common.user.uid = root
         .pointer("/info/2/0")
         .and_then(|v| v.as_u64());
 
-    let face_url = root
+    common.user.face_url = root
         .pointer("/info/0/15/user/base/face")
         .and_then(|v| v.as_str())
         .map(|s| s.to_string());
 
-    let media_ruid = root
+    common.medal.media_ruid = root
         .pointer("/info/0/15/user/medal/ruid")
         .and_then(|v| v.as_u64())
+        .filter(|v| *v != 0);
+
+    // info[3]：[粉丝牌等级, 粉丝牌名, 主播名, 房间号, ...]（未佩戴时为空数组）
+    common.medal.medal_level = root
+        .pointer("/info/3/0")
+        .and_then(|v| v.as_u64())
         .and_then(|v| u32::try_from(v).ok());
+    common.medal.medal_name = root
+        .pointer("/info/3/1")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
 
-    let color = root
+    if let Some(color) = root
         .pointer("/info/0/3")
         .and_then(|v| v.as_u64())
-        .map(rgb_decimal_to_hex);
+        .map(rgb_decimal_to_hex)
+    {
+        common.color = color;
+    }
 
-    let size = root
+    if let Some(size) = root
         .pointer("/info/0/2")
         .and_then(|v| v.as_u64())
-        .and_then(|v| u32::try_from(v).ok());
+        .and_then(|v| u32::try_from(v).ok())
+    {
+        common.size = size;
+    }
 
     // 尝试解析房管标记（不同地区/模式下字段可能缺失；缺失则按 false 处理）
-    let is_moderator = root
+    common.user.is_moderator = root
         .pointer("/info/2/2")
         .and_then(|v| v.as_i64())
         .map(|v| v == 1)
         .unwrap_or(false);
 
     // 舰队类型（blivedm/web.py: privilege_type）
-    let guard_level = root
-        .pointer("/info/7")
-        .and_then(|v| v.as_i64())
-        .unwrap_or(0)
-        .clamp(0, 3);
-
-    let mut msg = make_sse_danmu(text, user, color, size, face_url, media_ruid, time_ms);
-    if let Some(obj) = msg.as_object_mut() {
-        if let Some(uid) = uid {
-            obj.insert(
-                "uid".to_string(),
-                serde_json::Value::Number(serde_json::Number::from(uid)),
-            );
-        }
-        obj.insert("isModerator".to_string(), serde_json::Value::Bool(is_moderator));
-        obj.insert(
-            "guardLevel".to_string(),
-            serde_json::Value::Number(serde_json::Number::from(guard_level)),
-        );
-    }
-    Some(msg)
+    common.guard_level = guard_level_from(root.pointer("/info/7"));
+
+    Some(LiveEvent::Danmu(DanmuEvent { common }))
 }
 
-fn parse_open_live_danmaku(root: &serde_json::Value) -> Option<serde_json::Value> {
-    // 常见结构：{"cmd":"OPEN_LIVE_DANMAKU","data":{"uname":"xx","msg":"yy", ...}}
-    let data = root.get("data")?;
-    let text = data.get("msg")?.as_str()?.to_string();
-    let user = data
-        .get("uname")
+/// 开放平台各事件共有的用户/粉丝牌/舰队字段（blivedm/models/open_live.py）
+/// fans_medal_* 只描述本房间粉丝牌，因此 fans_medal_wearing_status 直接对应 hasOwnMedal
+fn fill_open_live_user(common: &mut EventCommon, user: &serde_json::Value, data: &serde_json::Value) {
+    common.user.uid = user.get("uid").and_then(|v| v.as_u64()).filter(|v| *v != 0);
+    common.user.face_url = user
+        .get("uface")
         .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
         .map(|s| s.to_string());
-    Some(make_sse_event("danmu", text, user, None, None, None, None, None))
+    common.user.is_moderator = data.get("is_admin").and_then(|v| v.as_i64()).unwrap_or(0) == 1;
+    common.guard_level = guard_level_from(data.get("guard_level"));
+    common.medal.has_own_medal = data
+        .get("fans_medal_wearing_status")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    common.medal.medal_name = data
+        .get("fans_medal_name")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string());
+    common.medal.medal_level = data
+        .get("fans_medal_level")
+        .and_then(|v| v.as_u64())
+        .filter(|v| *v != 0)
+        .and_then(|v| u32::try_from(v).ok());
 }
 
-fn parse_open_live_gift(root: &serde_json::Value) -> Option<serde_json::Value> {
-    // 常见结构：{"cmd":"OPEN_LIVE_GIFT","data":{"uname":"xx","gift_name":"xx","gift_num":1}}
-    let data = root.get("data")?;
-    let user = data
-        .get("uname")
+fn open_live_time_ms(data: &serde_json::Value) -> Option<u64> {
+    // 开放平台 timestamp 为 Unix 秒
+    data.get("timestamp")
+        .and_then(|v| v.as_u64())
+        .map(|sec| sec * 1000)
+}
+
+fn open_live_uname(user: &serde_json::Value) -> Option<String> {
+    user.get("uname")
         .and_then(|v| v.as_str())
-        .map(|s| s.to_string());
+        .map(|s| s.to_string())
+}
+
+fn parse_open_live_danmaku(root: &serde_json::Value) -> Option<LiveEvent> {
+    // 常见结构：{"cmd":"LIVE_OPEN_PLATFORM_DM","data":{"uname":"xx","uid":1,"uface":"..","msg":"yy","timestamp":秒,
+    //   "guard_level":0,"is_admin":0,"fans_medal_wearing_status":true,"fans_medal_name":"xx","fans_medal_level":1}}
+    let data = root.get("data")?;
+    let text = data.get("msg")?.as_str()?.to_string();
+    let mut common = EventCommon::new(text, open_live_uname(data), open_live_time_ms(data));
+    fill_open_live_user(&mut common, data, data);
+    Some(LiveEvent::Danmu(DanmuEvent { common }))
+}
+
+fn parse_open_live_gift(root: &serde_json::Value) -> Option<LiveEvent> {
+    // 常见结构：{"cmd":"LIVE_OPEN_PLATFORM_SEND_GIFT","data":{"uname":"xx","gift_name":"xx","gift_num":1,
+    //   "price":100,"paid":true, ...用户/粉丝牌字段同弹幕}}
+    let data = root.get("data")?;
     let gift_name = data.get("gift_name").and_then(|v| v.as_str()).unwrap_or("礼物");
     let gift_num = data.get("gift_num").and_then(|v| v.as_u64()).unwrap_or(1);
-    let text = format!("送出 {gift_name} x{gift_num}");
-    Some(make_sse_event("gift", text, user, None, None, None, None, None))
+    // price：礼物单价（1000 = 1元）
+    let price = data.get("price").and_then(|v| v.as_u64()).unwrap_or(0);
+    // 开放平台用 paid 区分付费礼物
+    let paid = data.get("paid").and_then(|v| v.as_bool()).unwrap_or(true);
+    let mut common = EventCommon::new(gift_text(gift_name, gift_num), open_live_uname(data), open_live_time_ms(data));
+    fill_open_live_user(&mut common, data, data);
+    Some(make_gift_event(
+        common,
+        gift_name,
+        gift_num,
+        if paid { "gold" } else { "silver" },
+        price,
+    ))
 }
 
-fn parse_open_live_super_chat(root: &serde_json::Value) -> Option<serde_json::Value> {
-    // 常见结构：{"cmd":"OPEN_LIVE_SUPER_CHAT","data":{"uname":"xx","message":"yy"}}
+fn parse_open_live_super_chat(root: &serde_json::Value) -> Option<LiveEvent> {
+    // 常见结构：{"cmd":"LIVE_OPEN_PLATFORM_SUPER_CHAT","data":{"uname":"xx","message":"yy","message_id":1,
+    //   "rmb":30,"start_time":秒,"end_time":秒, ...用户/粉丝牌字段同弹幕}}
     let data = root.get("data")?;
-    let user = data
-        .get("uname")
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string());
     let message = data
         .get("message")
         .or_else(|| data.get("msg"))
         .and_then(|v| v.as_str())
         .unwrap_or("");
-    let text = if message.is_empty() {
+    let price = data.get("rmb").and_then(|v| v.as_u64()).unwrap_or(0);
+    let start_time = data.get("start_time").and_then(|v| v.as_u64()).unwrap_or(0);
+    let end_time = data.get("end_time").and_then(|v| v.as_u64()).unwrap_or(start_time);
+    let mut common = EventCommon::new(super_chat_text(message), open_live_uname(data), open_live_time_ms(data));
+    fill_open_live_user(&mut common, data, data);
+    Some(LiveEvent::SuperChat(SuperChatEvent {
+        common,
+        sc_id: data.get("message_id").and_then(|v| v.as_u64()),
+        message: message.to_string(),
+        price,
+        duration: end_time.saturating_sub(start_time),
+    }))
+}
+
+fn parse_open_live_super_chat_del(root: &serde_json::Value) -> Option<LiveEvent> {
+    // {"cmd":"LIVE_OPEN_PLATFORM_SUPER_CHAT_DEL","data":{"room_id":1,"message_ids":[1,2]}}
+    let data = root.get("data")?;
+    let sc_ids = data
+        .get("message_ids")?
+        .as_array()?
+        .iter()
+        .filter_map(|v| v.as_u64())
+        .collect::<Vec<_>>();
+    Some(LiveEvent::SuperChatDelete(SuperChatDeleteEvent {
+        common: EventCommon::new(String::new(), Some(String::new()), None),
+        sc_ids,
+    }))
+}
+
+fn parse_open_live_guard(root: &serde_json::Value) -> Option<LiveEvent> {
+    // {"cmd":"LIVE_OPEN_PLATFORM_GUARD","data":{"user_info":{"uid":1,"uname":"xx","uface":".."},
+    //   "guard_level":3,"guard_num":1,"guard_unit":"月","price":198000,"timestamp":秒, ...粉丝牌字段}}
+    let data = root.get("data")?;
+    let user_info = data.get("user_info").unwrap_or(&serde_json::Value::Null);
+    let guard_level = guard_level_from(data.get("guard_level"));
+    let gift_name = match guard_level {
+        GuardLevel::Governor => "总督",
+        GuardLevel::Admiral => "提督",
+        _ => "舰长",
+    };
+    let num = data.get("guard_num").and_then(|v| v.as_u64()).unwrap_or(1);
+    let mut common = EventCommon::new(
+        format!("开通了 {gift_name} x{num}"),
+        open_live_uname(user_info),
+        open_live_time_ms(data),
+    );
+    fill_open_live_user(&mut common, user_info, data);
+    Some(LiveEvent::GuardBuy(GuardBuyEvent {
+        common,
+        gift_name: gift_name.to_string(),
+        num,
+        price: data.get("price").and_then(|v| v.as_u64()).unwrap_or(0),
+    }))
+}
+
+fn parse_open_live_like(root: &serde_json::Value) -> Option<LiveEvent> {
+    // {"cmd":"LIVE_OPEN_PLATFORM_LIKE","data":{"uname":"xx","uid":1,"uface":"..","like_text":"为主播点赞了","like_count":1, ...}}
+    let data = root.get("data")?;
+    let uname = data.get("uname").and_then(|v| v.as_str()).unwrap_or("匿名用户");
+    let like_text = data
+        .get("like_text")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .unwrap_or("点赞了直播间");
+    let mut common = EventCommon::new(format!("{uname} {like_text}"), Some(uname.to_string()), open_live_time_ms(data));
+    fill_open_live_user(&mut common, data, data);
+    Some(LiveEvent::Like(InteractEvent { common }))
+}
+
+fn parse_open_live_room_enter(root: &serde_json::Value) -> Option<LiveEvent> {
+    // {"cmd":"LIVE_OPEN_PLATFORM_LIVE_ROOM_ENTER","data":{"uname":"xx","uid":1,"uface":"..","timestamp":秒}}
+    let data = root.get("data")?;
+    let uname = data.get("uname").and_then(|v| v.as_str()).unwrap_or("匿名用户");
+    let mut common = EventCommon::new(format!("{uname} 进入直播间"), Some(uname.to_string()), open_live_time_ms(data));
+    fill_open_live_user(&mut common, data, data);
+    Some(LiveEvent::Enter(InteractEvent { common }))
+}
+
+fn parse_open_live_live_status(root: &serde_json::Value, started: bool) -> Option<LiveEvent> {
+    // {"cmd":"LIVE_OPEN_PLATFORM_LIVE_START","data":{"room_id":1,"title":"xx","area_name":"xx","timestamp":秒}}
+    let data = root.get("data")?;
+    let title = data.get("title").and_then(|v| v.as_str()).unwrap_or("").to_string();
+    let area_name = data.get("area_name").and_then(|v| v.as_str()).unwrap_or("").to_string();
+    let text = if started {
+        if title.is_empty() { "直播开始".to_string() } else { format!("直播开始：{title}") }
+    } else {
+        "直播结束".to_string()
+    };
+    // 系统事件，没有发送者
+    let event = LiveStatusEvent {
+        common: EventCommon::new(text, Some(String::new()), open_live_time_ms(data)),
+        title,
+        area_name,
+    };
+    Some(if started { LiveEvent::LiveStart(event) } else { LiveEvent::LiveEnd(event) })
+}
+
+pub(crate) fn gift_text(gift_name: &str, gift_num: u64) -> String {
+    format!("送出 {gift_name} x{gift_num}")
+}
+
+fn super_chat_text(message: &str) -> String {
+    if message.is_empty() {
         "醒目留言".to_string()
     } else {
         format!("醒目留言：{message}")
+    }
+}
+
+fn make_gift_event(common: EventCommon, gift_name: &str, gift_num: u64, coin_type: &str, price: u64) -> LiveEvent {
+    LiveEvent::Gift(GiftEvent {
+        common,
+        gift_name: gift_name.to_string(),
+        gift_num,
+        coin_type: coin_type.to_string(),
+        price,
+        total_coin: price.saturating_mul(gift_num),
+    })
+}
+
+fn guard_level_from(v: Option<&serde_json::Value>) -> GuardLevel {
+    v.and_then(|v| v.as_u64())
+        .and_then(|v| u8::try_from(v).ok())
+        .map(GuardLevel::from)
+        .unwrap_or_default()
+}
+
+fn medal_ruid_from(medal_info: Option<&serde_json::Value>) -> Option<u64> {
+    // medal_info.target_id：粉丝牌所属主播 uid（未佩戴时为 0；新账号 uid 超出 u32）
+    medal_info
+        .and_then(|m| m.get("target_id"))
+        .and_then(|v| v.as_u64())
+        .filter(|v| *v != 0)
+}
+
+fn parse_send_gift(root: &serde_json::Value) -> Option<LiveEvent> {
+    // 结构参考 blivedm/models/web.py GiftMessage：
+    // {"cmd":"SEND_GIFT","data":{"giftName":"xx","num":1,"uname":"xx","face":"..","uid":1,"timestamp":秒,
+    //   "coin_type":"gold","price":100,"total_coin":100,"guard_level":0,"medal_info":{"target_id":..}}}
+    let data = root.get("data")?;
+    let gift_name = data.get("giftName").and_then(|v| v.as_str()).unwrap_or("礼物");
+    let gift_num = data.get("num").and_then(|v| v.as_u64()).unwrap_or(1);
+    let user = data
+        .get("uname")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+    let time_ms = data
+        .get("timestamp")
+        .and_then(|v| v.as_u64())
+        .map(|sec| sec * 1000);
+
+    let mut common = EventCommon::new(gift_text(gift_name, gift_num), user, time_ms);
+    common.user.uid = data.get("uid").and_then(|v| v.as_u64());
+    common.user.face_url = data
+        .get("face")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+    common.guard_level = guard_level_from(data.get("guard_level"));
+    common.medal.media_ruid = medal_ruid_from(data.get("medal_info"));
+
+    let coin_type = data.get("coin_type").and_then(|v| v.as_str()).unwrap_or("silver");
+    let price = data.get("price").and_then(|v| v.as_u64()).unwrap_or(0);
+    Some(make_gift_event(common, gift_name, gift_num, coin_type, price))
+}
+
+fn parse_super_chat_message(root: &serde_json::Value) -> Option<LiveEvent> {
+    // 结构参考 blivedm/models/web.py SuperChatMessage：
+    // {"cmd":"SUPER_CHAT_MESSAGE","data":{"message":"xx","price":30,"time":60,"start_time":秒,"uid":1,
+    //   "user_info":{"uname":"xx","face":"..","guard_level":0},"medal_info":{"target_id":..}}}
+    let data = root.get("data")?;
+    let message = data.get("message").and_then(|v| v.as_str()).unwrap_or("");
+    let user_info = data.get("user_info");
+    let user = user_info
+        .and_then(|u| u.get("uname"))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+    let time_ms = data
+        .get("start_time")
+        .and_then(|v| v.as_u64())
+        .map(|sec| sec * 1000);
+
+    let mut common = EventCommon::new(super_chat_text(message), user, time_ms);
+    common.user.uid = data.get("uid").and_then(|v| v.as_u64());
+    common.user.face_url = user_info
+        .and_then(|u| u.get("face"))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+    common.guard_level = guard_level_from(user_info.and_then(|u| u.get("guard_level")));
+    common.medal.media_ruid = medal_ruid_from(data.get("medal_info"));
+
+    Some(LiveEvent::SuperChat(SuperChatEvent {
+        common,
+        sc_id: data.get("id").and_then(|v| v.as_u64()),
+        message: message.to_string(),
+        price: data.get("price").and_then(|v| v.as_u64()).unwrap_or(0),
+        // time：留言持续时间（秒）
+        duration: data.get("time").and_then(|v| v.as_u64()).unwrap_or(0),
+    }))
+}
+
+fn parse_super_chat_message_delete(root: &serde_json::Value) -> Option<LiveEvent> {
+    // blivedm/models/web.py: {"cmd":"SUPER_CHAT_MESSAGE_DELETE","data":{"ids":[1,2]}}
+    let data = root.get("data")?;
+    let sc_ids = data
+        .get("ids")?
+        .as_array()?
+        .iter()
+        .filter_map(|v| v.as_u64())
+        .collect::<Vec<_>>();
+    Some(LiveEvent::SuperChatDelete(SuperChatDeleteEvent {
+        common: EventCommon::new(String::new(), Some(String::new()), None),
+        sc_ids,
+    }))
+}
+
+fn parse_interact_word(root: &serde_json::Value) -> Option<LiveEvent> {
+    // 结构参考 blivedm/models/web.py InteractWordMessage：
+    // {"cmd":"INTERACT_WORD","data":{"uid":1,"uname":"xx","msg_type":1,"timestamp":秒,"fans_medal":{"target_id":..}}}
+    // msg_type: 1进入, 2关注, 3分享, 4特别关注, 5互相关注
+    let data = root.get("data")?;
+    let msg_type = data.get("msg_type").and_then(|v| v.as_u64())?;
+    let uname = data.get("uname").and_then(|v| v.as_str()).unwrap_or("匿名用户");
+    let time_ms = data
+        .get("timestamp")
+        .and_then(|v| v.as_u64())
+        .map(|sec| sec * 1000);
+
+    let text = match msg_type {
+        1 => format!("{uname} 进入直播间"),
+        2 | 4 | 5 => format!("{uname} 关注了直播间"),
+        3 => format!("{uname} 分享了直播间"),
+        _ => return None,
     };
-    Some(make_sse_event("superChat", text, user, None, None, None, None, None))
+    let mut common = EventCommon::new(text, Some(uname.to_string()), time_ms);
+    common.user.uid = data.get("uid").and_then(|v| v.as_u64());
+    common.user.face_url = data
+        .pointer("/uinfo/base/face")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+    common.medal.media_ruid = medal_ruid_from(data.get("fans_medal"));
+    common.guard_level = guard_level_from(data.pointer("/fans_medal/guard_level"));
+
+    let event = InteractEvent { common };
+    Some(match msg_type {
+        1 => LiveEvent::Enter(event),
+        3 => LiveEvent::Share(event),
+        _ => LiveEvent::Follow(event),
+    })
+}
+
+fn parse_entry_effect(root: &serde_json::Value) -> Option<LiveEvent> {
+    // 舰长/高能用户进场特效：{"cmd":"ENTRY_EFFECT","data":{"uid":1,"copy_writing":"欢迎舰长 <%xx%> 进入直播间",
+    //   "privilege_type":3,"face":"..","uinfo":{"base":{"name":"xx"}},"trigger_time":纳秒}}
+    let data = root.get("data")?;
+    let copy_writing = data.get("copy_writing").and_then(|v| v.as_str())?;
+    // <%xx%> 是高亮用户名的标记
+    let text = copy_writing.replace("<%", "").replace("%>", "");
+    let user = data
+        .pointer("/uinfo/base/name")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+    let time_ms = data
+        .get("trigger_time")
+        .and_then(|v| v.as_u64())
+        .map(|ns| ns / 1_000_000);
+
+    let mut common = EventCommon::new(text, user, time_ms);
+    common.user.uid = data.get("uid").and_then(|v| v.as_u64());
+    common.user.face_url = data
+        .get("face")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+    common.guard_level = guard_level_from(data.get("privilege_type"));
+
+    Some(LiveEvent::Enter(InteractEvent { common }))
+}
+
+fn parse_like_click(root: &serde_json::Value) -> Option<LiveEvent> {
+    // {"cmd":"LIKE_INFO_V3_CLICK","data":{"uid":1,"uname":"xx","like_text":"为主播点赞了","fans_medal":{"target_id":..}}}
+    let data = root.get("data")?;
+    let uname = data.get("uname").and_then(|v| v.as_str()).unwrap_or("匿名用户");
+    let like_text = data
+        .get("like_text")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .unwrap_or("点赞了直播间");
+
+    let mut common = EventCommon::new(format!("{uname} {like_text}"), Some(uname.to_string()), None);
+    common.user.uid = data.get("uid").and_then(|v| v.as_u64());
+    common.user.face_url = data
+        .pointer("/uinfo/base/face")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+    common.medal.media_ruid = medal_ruid_from(data.get("fans_medal"));
+    common.guard_level = guard_level_from(data.pointer("/fans_medal/guard_level"));
+
+    Some(LiveEvent::Like(InteractEvent { common }))
+}
+
+fn parse_watched_change(root: &serde_json::Value) -> Option<LiveEvent> {
+    // {"cmd":"WATCHED_CHANGE","data":{"num":12345,"text_small":"1.2万","text_large":"1.2万人看过"}}
+    let data = root.get("data")?;
+    let num = data.get("num").and_then(|v| v.as_u64())?;
+    let text = data
+        .get("text_large")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| format!("{num}人看过"));
+
+    // 系统事件，没有发送者
+    Some(LiveEvent::Watched(WatchedEvent {
+        common: EventCommon::new(text, Some(String::new()), None),
+        num,
+    }))
+}
+
+fn parse_guard_buy(root: &serde_json::Value) -> Option<LiveEvent> {
+    // 结构参考 blivedm/models/web.py GuardBuyMessage：
+    // {"cmd":"GUARD_BUY","data":{"uid":1,"username":"xx","guard_level":3,"num":1,"price":198000,
+    //   "gift_name":"舰长","start_time":秒}}
+    let data = root.get("data")?;
+    let guard_level = guard_level_from(data.get("guard_level"));
+    let gift_name = data
+        .get("gift_name")
+        .and_then(|v| v.as_str())
+        .unwrap_or(match guard_level {
+            GuardLevel::Governor => "总督",
+            GuardLevel::Admiral => "提督",
+            _ => "舰长",
+        });
+    let num = data.get("num").and_then(|v| v.as_u64()).unwrap_or(1);
+    let user = data
+        .get("username")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+    let time_ms = data
+        .get("start_time")
+        .and_then(|v| v.as_u64())
+        .map(|sec| sec * 1000);
+
+    let mut common = EventCommon::new(format!("开通了 {gift_name} x{num}"), user, time_ms);
+    common.user.uid = data.get("uid").and_then(|v| v.as_u64());
+    common.guard_level = guard_level;
+
+    Some(LiveEvent::GuardBuy(GuardBuyEvent {
+        common,
+        gift_name: gift_name.to_string(),
+        num,
+        price: data.get("price").and_then(|v| v.as_u64()).unwrap_or(0),
+    }))
 }
 
 const BILI_BROADCAST_WS: &str = "wss://broadcastlv.chat.bilibili.com/sub";
 
+// 重连退避上限：reconnect_interval * 2^n 封顶于此，再叠加随机抖动
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(60);
+// IP 被风控（HTTP 412 / getDanmuInfo -352）时的退避：短时间内重试只会延长封禁
+const RISK_CONTROL_BACKOFF: Duration = Duration::from_secs(600);
+// blivedm AuthReplyCode.TOKEN_ERROR
+const AUTH_REPLY_TOKEN_ERROR: i64 = -101;
+// B 站风控拦截：-352 风控校验失败，-412 请求被拦截
+const RISK_CONTROL_CODES: [i64; 2] = [-352, -412];
+
+/// 鉴权/风控失败分类，code() 作为 websocket-status 的 code 字段发给前端
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthFailure {
+    // OP_AUTH_REPLY code=-101：token 错误或过期，刷新一次 token 后仍失败则停止
+    TokenInvalid,
+    // 其它非 0 鉴权码：房间不存在/不可用，同样只重试一次
+    RoomInvalid,
+    // IP 被风控：长时间退避后再试
+    RiskControl,
+}
+
+impl AuthFailure {
+    fn code(&self) -> &'static str {
+        match self {
+            AuthFailure::TokenInvalid => "TOKEN_INVALID",
+            AuthFailure::RoomInvalid => "ROOM_INVALID",
+            // 与 AppError::RiskControl 的 code 一致
+            AuthFailure::RiskControl => "RISK_CONTROL",
+        }
+    }
+
+    fn from_auth_reply_code(code: i64) -> Self {
+        if code == AUTH_REPLY_TOKEN_ERROR {
+            AuthFailure::TokenInvalid
+        } else if RISK_CONTROL_CODES.contains(&code) {
+            AuthFailure::RiskControl
+        } else {
+            AuthFailure::RoomInvalid
+        }
+    }
+}
+
 const ROOM_INIT_URL: &str = "https://api.live.bilibili.com/room/v1/Room/get_info";
 const BUVID_INIT_URL: &str = "https://www.bilibili.com/";
 const WBI_INIT_URL: &str = "https://api.bilibili.com/x/web-interface/nav";
@@ -265,26 +959,98 @@ const OP_AUTH_REPLY: u32 = 8;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum RoomKeyType {
-    RoomId,   // 公屏房间直连
-    AuthCode, // 开放平台用户码
+    RoomId,        // 公屏房间直连
+    AuthCode,      // 开放平台用户码
+    TwitchChannel, // Twitch 频道名（IRC 匿名只读）
+    Replay,        // 回放录制文件（room_key 为文件路径）
 }
 
-#[derive(Debug, Default)]
+/// 单个房间连接的运行状态（按 connection_id 区分，可同时存在多个）
 struct WsState {
     handle: Option<JoinHandle<()>>,
+    // 事件管线任务（过滤 -> 样式 -> SSE）；断开时一并 abort，避免已排队的事件在“已断开”之后继续推送
+    pipeline: Option<JoinHandle<PipelineStats>>,
     should_stop: bool,
-    app_handle: Option<AppHandle>,
+    // 连接所用的平台实现（断开时调用其 disconnect 做平台侧清理）
+    source: Arc<dyn LiveSource>,
 
-    // 当前连接的房间标识（用于按房间样式配置）
-    current_room_key: Option<String>,
-    current_room_key_type: Option<RoomKeyType>,
+    // 该连接的房间标识
+    room_key: String,
+    room_key_type: RoomKeyType,
+    // 真实房间号（roomid 模式为解析短号后的房间号；开放平台为 anchor_info.room_id），用于给转发的事件打来源标记
+    room_id: Option<i64>,
 
     // roomid 模式下用于弹幕过滤（免登录）：主播 uid 与 face_url
     room_owner_uid: Option<i64>,
     room_owner_face_url: Option<String>,
+
+    // 开放平台模式：当前玩法会话（app/start 返回的 game_id）与 app/heartbeat 任务
+    open_live_game: Option<OpenLiveGame>,
+    open_live_heartbeat: Option<JoinHandle<()>>,
+
+    // 心跳回复人气值 / 高能榜人数 / 看过人数
+    metrics: LiveMetrics,
 }
 
-static WS_STATE: Lazy<Arc<RwLock<WsState>>> = Lazy::new(|| Arc::new(RwLock::new(WsState::default())));
+impl WsState {
+    fn new(room_key: String, room_key_type: RoomKeyType, source: Arc<dyn LiveSource>) -> Self {
+        Self {
+            handle: None,
+            pipeline: None,
+            should_stop: false,
+            source,
+            room_key,
+            room_key_type,
+            room_id: None,
+            room_owner_uid: None,
+            room_owner_face_url: None,
+            open_live_game: None,
+            open_live_heartbeat: None,
+            metrics: LiveMetrics::default(),
+        }
+    }
+}
+
+/// 直播间实时指标（供 get_status / /api/status 返回，并以 metrics SSE 事件推送给 overlay）
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveMetrics {
+    // OP_HEARTBEAT_REPLY 返回的人气值
+    pub popularity: Option<u64>,
+    // ONLINE_RANK_COUNT：高能榜人数 count 与在线人数 online_count
+    pub online_rank_count: Option<u64>,
+    pub online_count: Option<u64>,
+    // WATCHED_CHANGE：看过人数
+    pub watched: Option<u64>,
+    // 最近一次更新的 Unix 毫秒时间戳
+    pub updated_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionMetrics {
+    pub connection_id: String,
+    pub room_id: Option<i64>,
+    #[serde(flatten)]
+    pub metrics: LiveMetrics,
+}
+
+/// 对外展示的连接信息（list_connections 返回）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionInfo {
+    pub connection_id: String,
+    pub platform: &'static str,
+    pub room_key: String,
+    pub room_key_type: RoomKeyType,
+    pub room_id: Option<i64>,
+    pub running: bool,
+}
+
+// connection_id -> 连接状态
+static WS_CONNECTIONS: Lazy<Arc<RwLock<HashMap<String, WsState>>>> = Lazy::new(|| Arc::new(RwLock::new(HashMap::new())));
+// 所有连接共用同一个 AppHandle 发送前端事件（CLI 场景为 None）
+static APP_HANDLE: Lazy<Arc<RwLock<Option<AppHandle>>>> = Lazy::new(|| Arc::new(RwLock::new(None)));
 
 const BILI_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/102.0.0.0 Safari/537.36";
 
@@ -303,6 +1069,9 @@ struct RoomIdConnInfo {
 
     owner_uid: Option<i64>,
     owner_face_url: Option<String>,
+
+    // getDanmuInfo 被风控拦截（仍会回退 broadcastlv 尝试连接）
+    risk_control: bool,
 }
 
 async fn resolve_room_info(tmp_room_id: i64) -> (i64, Option<i64>) {
@@ -418,7 +1187,7 @@ async fn fetch_buvid() -> Option<String> {
     None
 }
 
-async fn fetch_wbi_key() -> Result<String, String> {
+async fn fetch_wbi_key() -> Result<String, AppError> {
     #[derive(Deserialize)]
     struct Resp {
         data: Option<Data>,
@@ -445,22 +1214,22 @@ async fn fetch_wbi_key() -> Result<String, String> {
         .header("User-Agent", BILI_USER_AGENT)
         .send()
         .await
-        .map_err(|e| format!("wbi 请求失败: {e:?}"))?;
+        .map_err(|e| AppError::Network(format!("wbi 请求失败: {e:?}")))?;
 
     if !res.status().is_success() {
-        return Err(format!(
+        return Err(AppError::Network(format!(
             "wbi 请求失败: status={}",
             res.status().as_u16()
-        ));
+        )));
     }
 
     let parsed: Resp = res
         .json()
         .await
-        .map_err(|e| format!("wbi 解析失败: {e:?}"))?;
+        .map_err(|e| AppError::BadResponse(format!("wbi 解析失败: {e:?}")))?;
     let wbi_img = parsed
         .data
-        .ok_or_else(|| "wbi data 缺失".to_string())?
+        .ok_or_else(|| AppError::BadResponse("wbi data 缺失".to_string()))?
         .wbi_img;
 
     fn extract_key(url: &str) -> String {
@@ -516,7 +1285,7 @@ fn wbi_add_sign(mut params: Vec<(String, String)>, wbi_key: &str) -> Vec<(String
     params
 }
 
-async fn fetch_danmaku_server(room_id: i64, wbi_key: &str) -> Result<(Vec<DanmakuHost>, Option<String>), String> {
+async fn fetch_danmaku_server(room_id: i64, wbi_key: &str) -> Result<(Vec<DanmakuHost>, Option<String>), AppError> {
     #[derive(Deserialize)]
     struct Resp {
         code: i64,
@@ -557,27 +1326,39 @@ async fn fetch_danmaku_server(room_id: i64, wbi_key: &str) -> Result<(Vec<Danmak
         .header("User-Agent", BILI_USER_AGENT)
         .send()
         .await
-        .map_err(|e| format!("getDanmuInfo 请求失败: {e:?}"))?;
+        .map_err(|e| AppError::Network(format!("getDanmuInfo 请求失败: {e:?}")))?;
 
+    if res.status().as_u16() == 412 {
+        return Err(AppError::RiskControl("getDanmuInfo 被风控拦截: status=412".to_string()));
+    }
     if !res.status().is_success() {
-        return Err(format!(
+        return Err(AppError::Network(format!(
             "getDanmuInfo 请求失败: status={} ",
             res.status().as_u16()
-        ));
+        )));
     }
 
     let parsed: Resp = res
         .json()
         .await
-        .map_err(|e| format!("getDanmuInfo 解析失败: {e:?}"))?;
-    if parsed.code != 0 {
-        return Err(format!(
-            "getDanmuInfo code={} message={}",
+        .map_err(|e| AppError::BadResponse(format!("getDanmuInfo 解析失败: {e:?}")))?;
+    if RISK_CONTROL_CODES.contains(&parsed.code) {
+        return Err(AppError::RiskControl(format!(
+            "getDanmuInfo 被风控拦截: code={} message={}",
             parsed.code,
             parsed.message.unwrap_or_default()
-        ));
+        )));
     }
-    let data = parsed.data.ok_or_else(|| "getDanmuInfo data 缺失".to_string())?;
+    if parsed.code != 0 {
+        return Err(AppError::Upstream {
+            api: "getDanmuInfo".to_string(),
+            code: parsed.code,
+            message: parsed.message.unwrap_or_default(),
+        });
+    }
+    let data = parsed
+        .data
+        .ok_or_else(|| AppError::BadResponse("getDanmuInfo data 缺失".to_string()))?;
     let hosts = data
         .host_list
         .into_iter()
@@ -594,7 +1375,17 @@ async fn fetch_danmaku_server(room_id: i64, wbi_key: &str) -> Result<(Vec<Danmak
     Ok((hosts, data.token))
 }
 
-async fn prepare_roomid_conn(tmp_room_id: i64) -> RoomIdConnInfo {
+/// 与 python 版一样按 host_list[retry % len] 轮换弹幕服务器；
+/// host_list 全部轮过一遍后才轮到默认 broadcastlv，之后再从头开始
+fn pick_ws_url(hosts: &[DanmakuHost], retry: usize) -> String {
+    let idx = retry % (hosts.len() + 1);
+    match hosts.get(idx) {
+        Some(h) => format!("wss://{}:{}/sub", h.host, h.wss_port),
+        std::option::Option::None => BILI_BROADCAST_WS.to_string(),
+    }
+}
+
+async fn prepare_roomid_conn(tmp_room_id: i64, retry: usize) -> RoomIdConnInfo {
     let (room_id, owner_uid) = resolve_room_info(tmp_room_id).await;
     let buvid = fetch_buvid().await;
 
@@ -604,15 +1395,19 @@ async fn prepare_roomid_conn(tmp_room_id: i64) -> RoomIdConnInfo {
     };
 
     // 获取弹幕服务器列表与 token（失败则回退到默认 broadcastlv）
+    let mut risk_control = false;
     let (ws_url, token) = match fetch_wbi_key().await {
         Ok(wbi_key) => match fetch_danmaku_server(room_id, &wbi_key).await {
             Ok((hosts, token)) if !hosts.is_empty() => {
-                // python 版是 host_list[retry % len]
-                let h = &hosts[0];
-                (format!("wss://{}:{}/sub", h.host, h.wss_port), token)
+                let ws_url = pick_ws_url(&hosts, retry);
+                if ws_debug_enabled() {
+                    eprintln!("[WebSocket][DEBUG] retry={retry} pick {ws_url}");
+                }
+                (ws_url, token)
             }
             Ok(_) => (BILI_BROADCAST_WS.to_string(), None),
             Err(e) => {
+                risk_control = matches!(e, AppError::RiskControl(_));
                 if ws_debug_enabled() {
                     eprintln!("[WebSocket][DEBUG] getDanmuInfo failed: {e}, fallback to {BILI_BROADCAST_WS}");
                 }
@@ -635,10 +1430,12 @@ async fn prepare_roomid_conn(tmp_room_id: i64) -> RoomIdConnInfo {
 
         owner_uid,
         owner_face_url,
+
+        risk_control,
     }
 }
 
-fn ws_debug_enabled() -> bool {
+pub(crate) fn ws_debug_enabled() -> bool {
     match WS_DEBUG_OVERRIDE.load(Ordering::Relaxed) {
         1 => return false,
         2 => return true,
@@ -654,8 +1451,59 @@ fn get_text_len(text: &str) -> u32 {
     text.chars().count() as u32
 }
 
-async fn should_forward_danmu(msg: &serde_json::Value) -> bool {
-    let cfg = DANMU_FILTER.read().await.clone();
+/// 返回 None 表示放行；allowed 为 allow 规则已放行（只检查禁言与用户黑名单）。
+/// local_dedup 为独立的去重窗口（按事件时间戳判断，用于 dry-run 与回放），为 None 时使用全局窗口
+async fn danmu_drop_reason(
+    filter: &ActiveFilter,
+    connection_id: &str,
+    event: &LiveEvent,
+    allowed: bool,
+    local_dedup: Option<&mut Deduper>,
+) -> Option<FilterDrop> {
+    let cfg = &filter.cfg;
+    let common = event.common();
+    let (uid, name) = (common.user.uid, common.user.name.as_str());
+
+    // 按用户的规则优先于其它条件：禁言 > 白名单 > 黑名单
+    if is_muted(uid).await {
+        return Some(FilterDrop::new(
+            DropReason::Muted,
+            format!("muted uid={:?} user={:?}", uid, name),
+        ));
+    }
+    if DanmuFilterConfig::user_in(&cfg.uid_whitelist, &cfg.user_whitelist, uid, name) {
+        return None;
+    }
+    if DanmuFilterConfig::user_in(&cfg.uid_blacklist, &cfg.user_blacklist, uid, name) {
+        return Some(FilterDrop::new(
+            DropReason::Blacklisted,
+            format!("user blacklist uid={:?} user={:?}", uid, name),
+        ));
+    }
+    if allowed {
+        return None;
+    }
+    if let Some(drop) = content_drop_reason(filter, connection_id, event).await {
+        return Some(drop);
+    }
+
+    // 去重放在最后：窗口只记录真正转发的弹幕，被其它条件丢弃的不会挡住之后的同内容弹幕
+    let user_key = match uid.filter(|uid| *uid != 0) {
+        Some(uid) => format!("uid:{uid}"),
+        None => format!("name:{name}"),
+    };
+    let duplicate = match local_dedup {
+        Some(deduper) => deduper.check(&cfg.dedup, &user_key, &common.text, common.timestamp),
+        None => crate::dedup::check(&cfg.dedup, &user_key, &common.text).await,
+    };
+    duplicate.map(|reason| FilterDrop::new(reason, format!("{:?} user={:?} text={:?}", reason, name, common.text)))
+}
+
+/// 长度、关键词、粉丝牌与主播条件
+async fn content_drop_reason(filter: &ActiveFilter, connection_id: &str, event: &LiveEvent) -> Option<FilterDrop> {
+    let cfg = &filter.cfg;
+    let common = event.common();
+
     // 兼容用户预期：只要开启了任一过滤条件，就应当生效。
     // 否则很多人只会打开“仅粉丝牌/仅主播”等单项开关，却忘了总开关 enabled。
     let effective_enabled = cfg.blacklist_enabled
@@ -665,98 +1513,70 @@ async fn should_forward_danmu(msg: &serde_json::Value) -> bool {
         || cfg.min_len.is_some()
         || cfg.max_len.is_some();
     if !effective_enabled {
-        return true;
+        return None;
     }
 
-    let text = msg.get("text").and_then(|v| v.as_str()).unwrap_or("");
+    let text = common.text.as_str();
     let text_len = get_text_len(text);
+    let filtered = |detail: String| Some(FilterDrop::new(DropReason::Filtered, detail));
 
     if let Some(min_len) = cfg.min_len {
         if text_len < min_len {
-            if ws_debug_enabled() {
-                eprintln!("[Filter][DEBUG] drop: text_len({text_len}) < min_len({min_len}) text={:?}", text);
-            }
-            return false;
+            return filtered(format!("text_len({text_len}) < min_len({min_len}) text={:?}", text));
         }
     }
     if let Some(max_len) = cfg.max_len {
         if text_len > max_len {
-            if ws_debug_enabled() {
-                eprintln!("[Filter][DEBUG] drop: text_len({text_len}) > max_len({max_len}) text={:?}", text);
-            }
-            return false;
+            return filtered(format!("text_len({text_len}) > max_len({max_len}) text={:?}", text));
         }
     }
 
-    if !cfg.keyword_blacklist.is_empty() {
-        for kw in &cfg.keyword_blacklist {
-            let kw = kw.trim();
-            if !kw.is_empty() && text.contains(kw) {
-                if ws_debug_enabled() {
-                    eprintln!("[Filter][DEBUG] drop: keyword={:?} text={:?}", kw, text);
-                }
-                return false;
-            }
-        }
+    if let Some(rule) = filter.keywords.find_match(text) {
+        return filtered(format!("{} text={:?}", rule, text));
     }
 
-    let (owner_uid, owner_face) = {
-        let guard = WS_STATE.read().await;
-        (guard.room_owner_uid, guard.room_owner_face_url.clone())
-    };
+    let (owner_uid, owner_face) = room_owner_of(connection_id).await;
 
-    if cfg.only_fans_medal {
+    // 开放平台直接给出是否佩戴本房间粉丝牌（fans_medal_wearing_status），无需再比对 uid
+    if cfg.only_fans_medal && !common.medal.has_own_medal {
         let Some(owner_uid) = owner_uid else {
-            if ws_debug_enabled() {
-                eprintln!("[Filter][DEBUG] drop: only_fans_medal but owner_uid is None");
-            }
-            return false;
+            return filtered("only_fans_medal but owner_uid is None".to_string());
         };
-        let owner_uid_str = owner_uid.to_string();
-        // media_ruid：佩戴本房间粉丝牌时为主播 uid；未佩戴时通常为 null
-        // forward_to_sse 会尽量保持数字类型，因此这里必须兼容 number/string/null
-        let media_ruid = msg.get("media_ruid").and_then(|v| {
-            if let Some(u) = v.as_u64() {
-                Some(u.to_string())
-            } else {
-                v.as_str().map(|s| s.to_string())
-            }
-        });
-        if media_ruid.as_deref() != Some(owner_uid_str.as_str()) {
-            if ws_debug_enabled() {
-                eprintln!(
-                    "[Filter][DEBUG] drop: only_fans_medal media_ruid={:?} owner_uid={} text={:?}",
-                    media_ruid,
-                    owner_uid,
-                    text
-                );
-            }
-            return false;
+        // media_ruid：佩戴本房间粉丝牌时为主播 uid；未佩戴时为 None
+        let media_ruid = common.medal.media_ruid;
+        if media_ruid.and_then(|v| i64::try_from(v).ok()) != Some(owner_uid) {
+            return filtered(format!(
+                "only_fans_medal media_ruid={:?} owner_uid={} text={:?}",
+                media_ruid, owner_uid, text
+            ));
         }
     }
 
     if cfg.only_streamer || cfg.hide_streamer {
-        let Some(owner_face) = owner_face else {
-            // 无法获取主播 face_url 时，为避免误判：only_streamer 直接过滤掉；hide_streamer 则不处理
-            return !cfg.only_streamer;
+        // 平台已直接标记主播（Twitch broadcaster 徽章）时无需比对 face_url
+        let is_streamer = if common.user.is_streamer {
+            true
+        } else {
+            let Some(owner_face) = owner_face else {
+                // 无法获取主播 face_url 时，为避免误判：only_streamer 直接过滤掉；hide_streamer 则不处理
+                return if cfg.only_streamer {
+                    filtered("only_streamer but owner face_url is None".to_string())
+                } else {
+                    None
+                };
+            };
+            let face_url = common.user.face_url.as_deref().unwrap_or("");
+            !face_url.is_empty() && face_url == owner_face
         };
-        let face_url = msg.get("face_url").and_then(|v| v.as_str()).unwrap_or("");
-        let is_streamer = !face_url.is_empty() && face_url == owner_face;
         if cfg.only_streamer && !is_streamer {
-            if ws_debug_enabled() {
-                eprintln!("[Filter][DEBUG] drop: only_streamer face_url={:?} owner_face={:?} text={:?}", face_url, owner_face, text);
-            }
-            return false;
+            return filtered(format!("only_streamer face_url={:?} text={:?}", common.user.face_url, text));
         }
         if cfg.hide_streamer && is_streamer {
-            if ws_debug_enabled() {
-                eprintln!("[Filter][DEBUG] drop: hide_streamer text={:?}", text);
-            }
-            return false;
+            return filtered(format!("hide_streamer text={:?}", text));
         }
     }
 
-    true
+    None
 }
 
 fn debug_dump_packet(prefix: &str, buf: impl AsRef<[u8]>) {
@@ -807,6 +1627,7 @@ async fn connect_with_ua(url: &str) -> Result<(tokio_tungstenite::WebSocketStrea
 
 pub async fn connect_websocket(
     app_handle: AppHandle,
+    connection_id: String,
     room_key: String,
     room_key_type: RoomKeyType,
     reconnect_interval: u64,
@@ -814,72 +1635,264 @@ pub async fn connect_websocket(
     open_live_app_id: Option<i64>,
     open_live_access_key_id: Option<String>,
     open_live_access_key_secret: Option<String>,
-) -> Result<(), String> {
-    {
-        // 关键点：确保 should_stop 被重置。
-        // 之前 disconnect 使用 abort，run_ws_loop 的 finally 不会执行，should_stop 会一直为 true，
-        // 导致下一次 connect 后任务立刻退出且不再发事件，前端就“看起来连不上”。
-        let mut guard = WS_STATE.write().await;
-
-        if let Some(h) = guard.handle.as_ref() {
-            if h.is_finished() {
-                guard.handle = None;
-            }
-        }
-        if guard.handle.is_some() {
-            return Err("已有连接".to_string());
-        }
-
-        guard.should_stop = false;
-        guard.app_handle = Some(app_handle.clone());
-        guard.current_room_key = Some(room_key.clone());
-        guard.current_room_key_type = Some(room_key_type);
-    }
-
-    emit_status("connecting", "连接中...").await;
-
-    let handle = tokio::spawn(run_ws_loop(
-        room_key,
+) -> Result<(), AppError> {
+    *APP_HANDLE.write().await = Some(app_handle);
+    let source = build_source(
+        &room_key,
         room_key_type,
         reconnect_interval,
         max_reconnect_attempts,
         open_live_app_id,
         open_live_access_key_id,
         open_live_access_key_secret,
-    ));
+    );
+    spawn_connection(connection_id, room_key, room_key_type, source).await
+}
 
-    {
-        let mut guard = WS_STATE.write().await;
-        guard.handle = Some(handle);
+/// 按连接类型选择平台实现
+fn build_source(
+    room_key: &str,
+    room_key_type: RoomKeyType,
+    reconnect_interval: u64,
+    max_reconnect_attempts: u32,
+    open_live_app_id: Option<i64>,
+    open_live_access_key_id: Option<String>,
+    open_live_access_key_secret: Option<String>,
+) -> Arc<dyn LiveSource> {
+    match room_key_type {
+        RoomKeyType::TwitchChannel => Arc::new(crate::twitch_client::TwitchChatSource::new(
+            room_key,
+            reconnect_interval,
+            max_reconnect_attempts,
+        )),
+        RoomKeyType::Replay => Arc::new(crate::replay_source::ReplaySource::new(room_key, 1.0)),
+        RoomKeyType::RoomId | RoomKeyType::AuthCode => Arc::new(BiliLiveSource {
+            room_key: room_key.to_string(),
+            room_key_type,
+            reconnect_interval,
+            max_reconnect_attempts,
+            open_live_app_id,
+            open_live_access_key_id,
+            open_live_access_key_secret,
+        }),
     }
+}
+
+/// 用任意平台实现建立连接；事件经 live_source::run_pipeline 进入过滤/样式/SSE 管线
+pub(crate) async fn spawn_connection(
+    connection_id: String,
+    room_key: String,
+    room_key_type: RoomKeyType,
+    source: Arc<dyn LiveSource>,
+) -> Result<(), AppError> {
+    // 检查、插入 WsState 与登记任务句柄在同一次写锁内完成，否则并发的同 id connect
+    // 可能在句柄登记前通过“运行中”检查并覆盖状态，导致前一个任务无法被断开。
+    // 同一个 connection_id 只允许一个运行中的任务；已结束的任务（达到最大重连次数等）直接覆盖。
+    // 注意：每次 connect 都新建 WsState，保证 should_stop 被重置（disconnect 使用 abort，任务的清理代码不会执行）。
+    let mut conns = WS_CONNECTIONS.write().await;
+    if let Some(existing) = conns.get(&connection_id) {
+        let running = existing.handle.as_ref().map(|h| !h.is_finished()).unwrap_or(false);
+        if running {
+            return Err(AppError::ConnectionExists(format!("连接 {connection_id} 已存在")));
+        }
+        // 旧任务已结束但管线可能还在处理剩余事件，覆盖前停掉，免得用新连接的房间信息补全旧事件
+        if let Some(pipeline) = &existing.pipeline {
+            pipeline.abort();
+        }
+    }
+
+    // 事件流：平台任务持有发送端，任务结束后管线处理完剩余事件自然退出；disconnect 时直接 abort
+    let (ctx, events) = SourceContext::new(connection_id.clone());
+    let replay = matches!(room_key_type, RoomKeyType::Replay);
+    let pipeline = tokio::spawn(run_pipeline(connection_id.clone(), events, replay));
+
+    let task_connection_id = connection_id.clone();
+    let task_source = source.clone();
+    // 任务在写锁释放后才会拿到 WS_CONNECTIONS，因此 "connecting" 一定先于平台自身的状态上报
+    let handle = tokio::spawn(async move {
+        emit_status(&task_connection_id, "connecting", "连接中...").await;
+
+        let platform = task_source.platform();
+        task_source.connect(ctx).await;
+
+        // 保留连接记录（list_connections 中显示为未运行），由 disconnect 或下一次同 id 的 connect 覆盖
+        if let Some(st) = WS_CONNECTIONS.write().await.get_mut(&task_connection_id) {
+            st.should_stop = false;
+            st.handle = None;
+        }
+        println!("[{platform}][{task_connection_id}] 任务结束");
+    });
+
+    let mut st = WsState::new(room_key, room_key_type, source);
+    st.handle = Some(handle);
+    st.pipeline = Some(pipeline);
+    conns.insert(connection_id, st);
     Ok(())
 }
 
-pub async fn disconnect_websocket() -> Result<(), String> {
-    let handle = {
-        let mut guard = WS_STATE.write().await;
-        guard.should_stop = true;
-        guard.handle.take()
+pub async fn disconnect_websocket(connection_id: &str) -> Result<(), AppError> {
+    let (handle, pipeline, source) = {
+        let mut conns = WS_CONNECTIONS.write().await;
+        let Some(st) = conns.get_mut(connection_id) else {
+            return Err(AppError::ConnectionNotFound(format!("连接 {connection_id} 不存在")));
+        };
+        st.should_stop = true;
+        (st.handle.take(), st.pipeline.take(), st.source.clone())
     };
     if let Some(h) = handle {
         h.abort();
         let _ = h.await;
     }
-
-    {
-        // abort 会跳过 run_ws_loop 的清理代码，因此这里必须兜底恢复状态
-        let mut guard = WS_STATE.write().await;
-        guard.should_stop = false;
-        guard.handle = None;
-        guard.room_owner_uid = None;
-        guard.room_owner_face_url = None;
+    if let Some(h) = pipeline {
+        h.abort();
+        let _ = h.await;
     }
 
-    emit_status("disconnected", "已断开连接").await;
+    // 平台侧清理（开放平台：停止 app/heartbeat 并调用 app/end，避免马上重连时报“已开启”）
+    source.disconnect(connection_id.to_string()).await;
+
+    // abort 会跳过 run_ws_loop 的清理代码，因此这里直接移除整条连接记录
+    WS_CONNECTIONS.write().await.remove(connection_id);
+
+    emit_status(connection_id, "disconnected", "已断开连接").await;
     Ok(())
 }
 
+/// 等待连接的平台任务结束且管线处理完队列里剩余的事件（回放 CLI / 回归测试），返回管线统计；
+/// 连接不存在或已被断开时返回 None
+pub async fn wait_pipeline(connection_id: &str) -> Option<PipelineStats> {
+    let pipeline = WS_CONNECTIONS.write().await.get_mut(connection_id)?.pipeline.take()?;
+    pipeline.await.ok()
+}
+
+/// 断开所有连接（CLI 退出等场景）
+pub async fn disconnect_all() {
+    let ids = WS_CONNECTIONS.read().await.keys().cloned().collect::<Vec<_>>();
+    for id in ids {
+        let _ = disconnect_websocket(&id).await;
+    }
+}
+
+pub async fn list_connections() -> Vec<ConnectionInfo> {
+    let conns = WS_CONNECTIONS.read().await;
+    let mut list = conns
+        .iter()
+        .map(|(id, st)| ConnectionInfo {
+            connection_id: id.clone(),
+            platform: st.source.platform(),
+            room_key: st.room_key.clone(),
+            room_key_type: st.room_key_type,
+            room_id: st.room_id,
+            running: st.handle.as_ref().map(|h| !h.is_finished()).unwrap_or(false),
+        })
+        .collect::<Vec<_>>();
+    list.sort_by(|a, b| a.connection_id.cmp(&b.connection_id));
+    list
+}
+
+pub async fn list_metrics() -> Vec<ConnectionMetrics> {
+    let conns = WS_CONNECTIONS.read().await;
+    let mut list = conns
+        .iter()
+        .map(|(id, st)| ConnectionMetrics {
+            connection_id: id.clone(),
+            room_id: st.room_id,
+            metrics: st.metrics.clone(),
+        })
+        .collect::<Vec<_>>();
+    list.sort_by(|a, b| a.connection_id.cmp(&b.connection_id));
+    list
+}
+
+/// 更新该连接的指标，并以 {"type":"metrics", ...} 推送给所有 SSE 连接
+async fn update_metrics(connection_id: &str, update: impl FnOnce(&mut LiveMetrics)) {
+    let snapshot = {
+        let mut conns = WS_CONNECTIONS.write().await;
+        let Some(st) = conns.get_mut(connection_id) else {
+            return;
+        };
+        update(&mut st.metrics);
+        st.metrics.updated_at = Some(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64,
+        );
+        ConnectionMetrics {
+            connection_id: connection_id.to_string(),
+            room_id: st.room_id,
+            metrics: st.metrics.clone(),
+        }
+    };
+
+    if let Some(state) = crate::get_sse_state().await {
+        let mut msg = serde_json::to_value(&snapshot).unwrap_or_default();
+        if let serde_json::Value::Object(ref mut map) = msg {
+            map.insert("type".to_string(), serde_json::Value::String("metrics".to_string()));
+        }
+        crate::sse_server::send_to_all_connections(&state, msg).await;
+    }
+}
+
+pub(crate) async fn set_connection_room(
+    connection_id: &str,
+    room_id: Option<i64>,
+    owner_uid: Option<i64>,
+    owner_face_url: Option<String>,
+) {
+    if let Some(st) = WS_CONNECTIONS.write().await.get_mut(connection_id) {
+        st.room_id = room_id;
+        st.room_owner_uid = owner_uid;
+        st.room_owner_face_url = owner_face_url;
+    }
+}
+
+pub(crate) async fn should_stop(connection_id: &str) -> bool {
+    // 连接记录已被移除（disconnect）时同样视为停止
+    WS_CONNECTIONS
+        .read()
+        .await
+        .get(connection_id)
+        .map(|st| st.should_stop)
+        .unwrap_or(true)
+}
+
+async fn room_owner_of(connection_id: &str) -> (Option<i64>, Option<String>) {
+    let conns = WS_CONNECTIONS.read().await;
+    match conns.get(connection_id) {
+        Some(st) => (st.room_owner_uid, st.room_owner_face_url.clone()),
+        std::option::Option::None => (None, None),
+    }
+}
+
+/// 指数退避：reconnect_interval * 2^(failures-1)，封顶 RECONNECT_BACKOFF_MAX，
+/// 再取 [delay/2, delay] 之间的随机值，避免多个连接同时断开后一起重连
+pub(crate) fn reconnect_backoff(reconnect_interval: u64, failures: u32) -> Duration {
+    let base = reconnect_interval.max(1000);
+    let exp = failures.saturating_sub(1).min(16);
+    let delay = base
+        .saturating_mul(1u64 << exp)
+        .min(RECONNECT_BACKOFF_MAX.as_millis() as u64);
+    let half = delay / 2;
+    Duration::from_millis(half + random_u64() % (delay - half + 1))
+}
+
+pub(crate) fn random_u64() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+
+    // 不为抖动单独引入 rand：RandomState 每次构造的 key 都是随机的
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u128(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+    hasher.finish()
+}
+
 async fn run_ws_loop(
+    ctx: SourceContext,
     room_key: String,
     room_key_type: RoomKeyType,
     reconnect_interval: u64,
@@ -888,15 +1901,20 @@ async fn run_ws_loop(
     open_live_access_key_id: Option<String>,
     open_live_access_key_secret: Option<String>,
 ) {
+    let connection_id = ctx.connection_id().to_string();
     let mut attempts = 0;
-    let reconnect_dur = Duration::from_millis(reconnect_interval.max(1000));
+    // 连接轮次（含成功后断开），用于轮换弹幕服务器；不随连接成功清零，避免断线后又连回同一台坏掉的服务器
+    let mut retry: usize = 0;
+    // token/房间鉴权失败后是否已刷新过一次 token（重新走 getDanmuInfo / app/start）
+    let mut auth_refreshed = false;
 
     while attempts <= max_reconnect_attempts {
-        {
-            let guard = WS_STATE.read().await;
-            if guard.should_stop {
-                break;
-            }
+        // 本轮鉴权/风控失败分类；None 表示普通断线
+        let mut auth_failure: Option<AuthFailure> = None;
+        let mut room_risk_control = false;
+
+        if ctx.should_stop().await {
+            break;
         }
 
         let ws_result = match room_key_type {
@@ -905,47 +1923,62 @@ async fn run_ws_loop(
                     Ok(id) => id,
                     Err(_) => {
                         eprintln!("[WebSocket] room_id 不是数字: {}", room_key);
-                        emit_status("error", "房间号不是数字").await;
+                        emit_status(&connection_id, "error", "房间号不是数字").await;
                         return;
                     }
                 };
-                let info = prepare_roomid_conn(tmp_room_id).await;
+                let info = prepare_roomid_conn(tmp_room_id, retry).await;
+                room_risk_control = info.risk_control;
                 let ws_url = info.ws_url.clone();
                 connect_with_ua(&ws_url)
                     .await
                     .map(|(ws, resp)| (ws, resp, Some(info), None))
             }
-            RoomKeyType::AuthCode => match start_game_and_get_ws(
-                &room_key,
-                open_live_app_id,
-                open_live_access_key_id.clone(),
-                open_live_access_key_secret.clone(),
-            )
+            RoomKeyType::AuthCode => match async {
+                // 重连前先结束上一轮玩法会话，否则 app/start 会因“已开启”失败
+                end_open_live_game(&connection_id).await;
+                let credentials = OpenLiveCredentials::resolve(
+                    open_live_app_id,
+                    open_live_access_key_id.clone(),
+                    open_live_access_key_secret.clone(),
+                )?;
+                start_game_and_get_ws(&room_key, credentials).await
+            }
             .await {
-                Ok(info) => connect_with_ua(&info.ws_url)
-                    .await
-                    .map(|(ws, resp)| (ws, resp, None, Some(info.auth_body))),
+                Ok(info) => {
+                    let heartbeat = tokio::spawn(run_open_live_heartbeat(connection_id.clone(), info.game.clone()));
+                    if let Some(st) = WS_CONNECTIONS.write().await.get_mut(&connection_id) {
+                        st.room_id = info.room_id;
+                        st.open_live_game = Some(info.game.clone());
+                        st.open_live_heartbeat = Some(heartbeat);
+                    } else {
+                        heartbeat.abort();
+                    }
+                    connect_with_ua(&info.ws_url)
+                        .await
+                        .map(|(ws, resp)| (ws, resp, None, Some(info.auth_body)))
+                }
                 Err(e) => {
-                    eprintln!("[WebSocket] start_game 失败: {e}");
-                    emit_status("error", &format!("鉴权失败: {}", e)).await;
+                    eprintln!("[WebSocket][{connection_id}] start_game 失败: {e}");
+                    emit_status_with_code(&connection_id, "error", Some(e.code()), &format!("鉴权失败: {}", e)).await;
                     return;
                 }
             },
+            // Twitch / 回放由各自的 LiveSource 处理，不会走到这里
+            RoomKeyType::TwitchChannel | RoomKeyType::Replay => return,
         };
 
         match ws_result {
             Ok((ws_stream, _resp, maybe_room_info, maybe_auth_body)) => {
-                println!("[WebSocket] 连接成功");
-                emit_status("connected", "连接成功").await;
+                println!("[WebSocket][{connection_id}] 连接成功");
+                emit_status(&connection_id, "connected", "连接成功").await;
                 attempts = 0;
                 let (mut write, mut read) = ws_stream.split();
 
-                // roomid 模式下保存主播 uid/face_url，用于后续弹幕过滤
+                // roomid 模式下保存真实房间号与主播 uid/face_url，用于后续弹幕过滤与来源标记
                 if let RoomKeyType::RoomId = room_key_type {
                     if let Some(info) = maybe_room_info.as_ref() {
-                        let mut guard = WS_STATE.write().await;
-                        guard.room_owner_uid = info.owner_uid;
-                        guard.room_owner_face_url = info.owner_face_url.clone();
+                        ctx.set_room(Some(info.room_id), info.owner_uid, info.owner_face_url.clone()).await;
                     }
                 }
 
@@ -985,46 +2018,65 @@ async fn run_ws_loop(
                             let _ = write.send(msg).await;
                         }
                     }
+                    RoomKeyType::TwitchChannel | RoomKeyType::Replay => {}
                 }
 
                 let mut heartbeat = tokio::time::interval(Duration::from_secs(20));
+                // 半开的 TCP 连接不会报错，read.next() 会一直挂起：按最后收到数据的时间判断是否僵死
+                let mut watchdog = tokio::time::interval(Duration::from_secs(5));
+                let mut last_seen = tokio::time::Instant::now();
 
                 loop {
                     tokio::select! {
+                        _ = watchdog.tick() => {
+                            let timeout_secs = HEARTBEAT_TIMEOUT_SECS.load(Ordering::Relaxed);
+                            if timeout_secs > 0 && last_seen.elapsed() > Duration::from_secs(timeout_secs) {
+                                eprintln!("[WebSocket][{connection_id}] {timeout_secs}s 未收到服务端数据，判定连接僵死，准备重连");
+                                emit_status(&connection_id, "stale", &format!("{}s 未收到心跳回复，正在重连", timeout_secs)).await;
+                                break;
+                            }
+                        }
                         _ = heartbeat.tick() => {
                             let msg = make_packet(Vec::new(), OP_HEARTBEAT, PROTO_VER_CLIENT);
                             if let Message::Binary(b) = &msg {
                                 debug_dump_packet("send heartbeat", b);
                             }
                             if let Err(e) = write.send(msg).await {
-                                eprintln!("[WebSocket] 心跳失败: {e}");
-                                emit_status("disconnected", "心跳失败").await;
+                                eprintln!("[WebSocket][{connection_id}] 心跳失败: {e}");
+                                emit_status(&connection_id, "disconnected", "心跳失败").await;
                                 break;
                             }
                         }
                         msg = read.next() => {
+                            if matches!(msg, Some(Ok(_))) {
+                                last_seen = tokio::time::Instant::now();
+                            }
                             match msg {
                                 Some(Ok(Message::Binary(bin))) => {
                                     debug_dump_packet("recv frame", &bin);
-                                    handle_packet(bin.as_ref()).await
+                                    record_packet(&connection_id, bin.as_ref()).await;
+                                    if let Err(failure) = handle_packet(&ctx, bin.as_ref()).await {
+                                        auth_failure = Some(failure);
+                                        break;
+                                    }
                                 }
-                                Some(Ok(Message::Text(txt))) => { eprintln!("[WebSocket] 收到文本帧（异常）: {txt}"); }
+                                Some(Ok(Message::Text(txt))) => { eprintln!("[WebSocket][{connection_id}] 收到文本帧（异常）: {txt}"); }
                                 Some(Ok(Message::Close(frame))) => { 
-                                    eprintln!("[WebSocket] 服务端关闭: {:?}", frame); 
-                                    emit_status("disconnected", "服务端关闭").await;
+                                    eprintln!("[WebSocket][{connection_id}] 服务端关闭: {:?}", frame); 
+                                    emit_status(&connection_id, "disconnected", "服务端关闭").await;
                                     break; 
                                 }
                                 Some(Ok(Message::Ping(_))) => {}
                                 Some(Ok(Message::Pong(_))) => {}
                                 Some(Ok(Message::Frame(_))) => {}
                                 Some(Err(e)) => { 
-                                    eprintln!("[WebSocket] 读取错误: {e}"); 
-                                    emit_status("disconnected", &format!("读取错误: {}", e)).await;
+                                    eprintln!("[WebSocket][{connection_id}] 读取错误: {e}"); 
+                                    emit_status(&connection_id, "disconnected", &format!("读取错误: {}", e)).await;
                                     break; 
                                 }
                                 std::option::Option::None => { 
-                                    println!("[WebSocket] 连接结束"); 
-                                    emit_status("disconnected", "连接结束").await;
+                                    println!("[WebSocket][{connection_id}] 连接结束"); 
+                                    emit_status(&connection_id, "disconnected", "连接结束").await;
                                     break; 
                                 }
                             }
@@ -1032,26 +2084,63 @@ async fn run_ws_loop(
                     }
                 }
             }
-            Err(_) => {
+            Err(e) => {
                 attempts += 1;
-                emit_status("reconnecting", &format!("重连中 {}/{}", attempts, max_reconnect_attempts)).await;
+                // 握手被 412 拒绝即 IP 风控；getDanmuInfo 被拦截后回退 broadcastlv 仍失败也按风控处理
+                let handshake_412 = matches!(&e, tungstenite::Error::Http(resp) if resp.status().as_u16() == 412);
+                if handshake_412 || room_risk_control {
+                    auth_failure = Some(AuthFailure::RiskControl);
+                } else {
+                    emit_status(&connection_id, "reconnecting", &format!("重连中 {}/{}", attempts, max_reconnect_attempts)).await;
+                }
+            }
+        }
+
+        match auth_failure {
+            Some(failure @ (AuthFailure::TokenInvalid | AuthFailure::RoomInvalid)) => {
+                if auth_refreshed {
+                    eprintln!("[WebSocket][{connection_id}] 刷新 token 后鉴权仍失败（{:?}），停止重连", failure);
+                    emit_status_with_code(&connection_id, "error", Some(failure.code()), "鉴权失败，已停止重连").await;
+                    break;
+                }
+                // 先刷新一次 token（下一轮会重新请求 getDanmuInfo / app/start），立即重试
+                auth_refreshed = true;
+                eprintln!("[WebSocket][{connection_id}] 鉴权失败（{:?}），刷新 token 后重试", failure);
+                emit_status_with_code(&connection_id, "reconnecting", Some(failure.code()), "鉴权失败，刷新 token 后重试").await;
+                retry = retry.wrapping_add(1);
+                continue;
+            }
+            Some(AuthFailure::RiskControl) => {
+                let delay = RISK_CONTROL_BACKOFF;
+                eprintln!("[WebSocket][{connection_id}] 疑似 IP 被风控，{}s 后重试", delay.as_secs());
+                emit_status_with_code(
+                    &connection_id,
+                    "reconnecting",
+                    Some(AuthFailure::RiskControl.code()),
+                    &format!("疑似被风控，{}s 后重试", delay.as_secs()),
+                )
+                .await;
+                retry = retry.wrapping_add(1);
+                tokio::time::sleep(delay).await;
+                continue;
+            }
+            std::option::Option::None => {
+                // 本轮鉴权通过（或未到鉴权就断开），允许下次鉴权失败时再刷新一次 token
+                auth_refreshed = false;
             }
         }
 
         if attempts > max_reconnect_attempts {
-            eprintln!("[WebSocket] 达到最大重连次数，停止");
+            eprintln!("[WebSocket][{connection_id}] 达到最大重连次数，停止");
             break;
         }
-        tokio::time::sleep(reconnect_dur).await;
-        println!("[WebSocket] 重连中 {attempts}/{max_reconnect_attempts} …");
+        retry = retry.wrapping_add(1);
+        let delay = reconnect_backoff(reconnect_interval, attempts);
+        println!("[WebSocket][{connection_id}] {}ms 后重连 {attempts}/{max_reconnect_attempts} …", delay.as_millis());
+        tokio::time::sleep(delay).await;
     }
 
-    {
-        let mut guard = WS_STATE.write().await;
-        guard.should_stop = false;
-        guard.handle = None;
-    }
-    println!("[WebSocket] 任务结束");
+    end_open_live_game(&connection_id).await;
 }
 
 fn make_packet(body: Vec<u8>, op: u32, proto_ver: u16) -> Message {
@@ -1066,7 +2155,9 @@ fn make_packet(body: Vec<u8>, op: u32, proto_ver: u16) -> Message {
     Message::Binary(buf.into())
 }
 
-async fn handle_packet(data: &[u8]) {
+/// 鉴权失败时返回失败分类，由 run_ws_loop 决定刷新 token、长时间退避还是停止
+pub(crate) async fn handle_packet(ctx: &SourceContext, data: &[u8]) -> Result<(), AuthFailure> {
+    let connection_id = ctx.connection_id();
     let mut packets = vec![data.to_vec()];
 
     while let Some(current_data) = packets.pop() {
@@ -1117,16 +2208,19 @@ async fn handle_packet(data: &[u8]) {
                     OP_HEARTBEAT_REPLY => {
                         if body.len() >= 4 {
                             let popularity = u32::from_be_bytes(body[0..4].try_into().unwrap());
-                            println!("[WebSocket] 心跳回复 人气={}", popularity);
+                            if ws_debug_enabled() {
+                                println!("[WebSocket][{connection_id}] 心跳回复 人气={}", popularity);
+                            }
+                            update_metrics(connection_id, |m| m.popularity = Some(u64::from(popularity))).await;
                         }
                     }
                     OP_AUTH_REPLY => {
                         match serde_json::from_slice::<serde_json::Value>(body) {
                             Ok(v) => {
-                                eprintln!("[WebSocket] 鉴权回复: {}", v);
-                                if v.get("code").and_then(|c| c.as_i64()).unwrap_or(0) != 0 {
-                                    emit_status("disconnected", &format!("鉴权失败 code={}", v["code"])).await;
-                                    break;
+                                eprintln!("[WebSocket][{connection_id}] 鉴权回复: {}", v);
+                                let code = v.get("code").and_then(|c| c.as_i64()).unwrap_or(0);
+                                if code != 0 {
+                                    return Err(AuthFailure::from_auth_reply_code(code));
                                 }
                             }
                             Err(_) => println!("[WebSocket] 鉴权成功"),
@@ -1134,7 +2228,7 @@ async fn handle_packet(data: &[u8]) {
                     }
                     OP_MESSAGE => {
                         if let Ok(text) = std::str::from_utf8(body) {
-                            handle_command_text(text).await;
+                            handle_command_text(ctx, text).await;
                         }
                     }
                     _ => {}
@@ -1143,36 +2237,115 @@ async fn handle_packet(data: &[u8]) {
             offset += packet_len;
         }
     }
+    Ok(())
 }
 
-async fn handle_command_text(text: &str) {
+/// 只负责解析：解析结果经 ctx.emit 进入事件管线，过滤与推送见 dispatch_event
+async fn handle_command_text(ctx: &SourceContext, text: &str) {
+    let connection_id = ctx.connection_id();
     if let Ok(val) = serde_json::from_str::<serde_json::Value>(text) {
         if let Some(cmd) = val.get("cmd").and_then(|c| c.as_str()) {
             let cmd = cmd.split(':').next().unwrap_or(cmd);
-            // 公屏 DANMU_MSG，开放平台 OPEN_LIVE_* 事件
+            // 公屏 DANMU_MSG/SEND_GIFT/SUPER_CHAT_MESSAGE/GUARD_BUY，开放平台 LIVE_OPEN_PLATFORM_*（旧名 OPEN_LIVE_*）事件
+            // 互动类（INTERACT_WORD/ENTRY_EFFECT/LIKE_INFO_V3_CLICK/WATCHED_CHANGE）按通用设置里的开关转发
             match cmd {
                 "DANMU_MSG" => {
                     // println!("[WebSocket] 弹幕原始信息 {}", serde_json::to_string_pretty(&val).unwrap_or_default());
                     if let Some(msg) = parse_danmu_msg(&val) {
                         println!("[WebSocket] 弹幕解析结果 {}", serde_json::to_string_pretty(&msg).unwrap_or_default());
-                        if should_forward_danmu(&msg).await {
-                            forward_to_sse(msg).await;
+                        ctx.emit(msg).await;
+                    }
+                }
+                "SEND_GIFT" => {
+                    if let Some(msg) = parse_send_gift(&val) {
+                        ctx.emit(msg).await;
+                    }
+                }
+                "SUPER_CHAT_MESSAGE" => {
+                    if let Some(msg) = parse_super_chat_message(&val) {
+                        ctx.emit(msg).await;
+                    }
+                }
+                "SUPER_CHAT_MESSAGE_DELETE" => {
+                    if let Some(msg) = parse_super_chat_message_delete(&val) {
+                        ctx.emit(msg).await;
+                    }
+                }
+                "GUARD_BUY" => {
+                    if let Some(msg) = parse_guard_buy(&val) {
+                        ctx.emit(msg).await;
+                    }
+                }
+                "ONLINE_RANK_COUNT" => {
+                    // {"cmd":"ONLINE_RANK_COUNT","data":{"count":123,"online_count":456}}
+                    if let Some(data) = val.get("data") {
+                        let count = data.get("count").and_then(|v| v.as_u64());
+                        let online_count = data.get("online_count").and_then(|v| v.as_u64());
+                        update_metrics(connection_id, |m| {
+                            if count.is_some() {
+                                m.online_rank_count = count;
+                            }
+                            if online_count.is_some() {
+                                m.online_count = online_count;
+                            }
+                        })
+                        .await;
+                    }
+                }
+                "INTERACT_WORD" | "ENTRY_EFFECT" | "LIKE_INFO_V3_CLICK" | "WATCHED_CHANGE" => {
+                    let parsed = match cmd {
+                        "INTERACT_WORD" => parse_interact_word(&val),
+                        "ENTRY_EFFECT" => parse_entry_effect(&val),
+                        "LIKE_INFO_V3_CLICK" => parse_like_click(&val),
+                        _ => parse_watched_change(&val),
+                    };
+                    if let Some(msg) = parsed {
+                        // 看过人数无论是否转发为字幕都计入指标
+                        if let LiveEvent::Watched(watched) = &msg {
+                            let num = watched.num;
+                            update_metrics(connection_id, |m| m.watched = Some(num)).await;
                         }
+                        ctx.emit(msg).await;
                     }
                 }
-                "OPEN_LIVE_DANMAKU" => {
+                "LIVE_OPEN_PLATFORM_DM" | "OPEN_LIVE_DANMAKU" => {
                     if let Some(msg) = parse_open_live_danmaku(&val) {
-                        forward_to_sse(msg).await;
+                        ctx.emit(msg).await;
                     }
                 }
-                "OPEN_LIVE_GIFT" => {
+                "LIVE_OPEN_PLATFORM_SEND_GIFT" | "OPEN_LIVE_GIFT" => {
                     if let Some(msg) = parse_open_live_gift(&val) {
-                        forward_to_sse(msg).await;
+                        ctx.emit(msg).await;
                     }
                 }
-                "OPEN_LIVE_SUPER_CHAT" => {
+                "LIVE_OPEN_PLATFORM_SUPER_CHAT" | "OPEN_LIVE_SUPER_CHAT" => {
                     if let Some(msg) = parse_open_live_super_chat(&val) {
-                        forward_to_sse(msg).await;
+                        ctx.emit(msg).await;
+                    }
+                }
+                "LIVE_OPEN_PLATFORM_SUPER_CHAT_DEL" | "OPEN_LIVE_SUPER_CHAT_DEL" => {
+                    if let Some(msg) = parse_open_live_super_chat_del(&val) {
+                        ctx.emit(msg).await;
+                    }
+                }
+                "LIVE_OPEN_PLATFORM_GUARD" => {
+                    if let Some(msg) = parse_open_live_guard(&val) {
+                        ctx.emit(msg).await;
+                    }
+                }
+                "LIVE_OPEN_PLATFORM_LIKE" | "LIVE_OPEN_PLATFORM_LIVE_ROOM_ENTER" => {
+                    let parsed = if cmd == "LIVE_OPEN_PLATFORM_LIKE" {
+                        parse_open_live_like(&val)
+                    } else {
+                        parse_open_live_room_enter(&val)
+                    };
+                    if let Some(msg) = parsed {
+                        ctx.emit(msg).await;
+                    }
+                }
+                "LIVE_OPEN_PLATFORM_LIVE_START" | "LIVE_OPEN_PLATFORM_LIVE_END" => {
+                    if let Some(msg) = parse_open_live_live_status(&val, cmd == "LIVE_OPEN_PLATFORM_LIVE_START") {
+                        ctx.emit(msg).await;
                     }
                 }
                 _ => {}
@@ -1183,97 +2356,221 @@ async fn handle_command_text(text: &str) {
     }
 }
 
-async fn forward_to_sse(val: serde_json::Value) {
-    if let Some(state) = crate::get_sse_state().await {
-        // 兜底补齐 hasOwnMedal / isModerator / isStreamer / guardLevel
-        let mut val = val;
-        if let Some(obj) = val.as_object_mut() {
-            if obj.get("isModerator").and_then(|v| v.as_bool()).is_none() {
-                obj.insert("isModerator".to_string(), serde_json::Value::Bool(false));
-            }
+/// 事件管线（live_source::run_pipeline）的入口：弹幕过滤与互动事件开关对所有平台生效
+/// replay 为 Some 表示回放连接：去重改用按事件时间戳判断的独立窗口（加速回放时墙钟窗口会误判），
+/// 且不写入历史记录、不计入排行榜与弹幕分析；直播为 None 使用全局窗口
+pub(crate) async fn dispatch_event(connection_id: &str, event: LiveEvent, replay: Option<&mut Deduper>) -> Dispatched {
+    // 弹幕分析按过滤前的原始弹幕统计，否则刷屏高峰被去重/限速挡掉后就检测不到了；
+    // 回放事件的时间与直播无关，不计入按墙钟统计的分析窗口
+    let sample = if replay.is_none() { crate::analytics::sample_of(&event) } else { None };
+    let outcome = filter_and_forward(connection_id, event, replay).await;
+    if let Some(sample) = sample {
+        crate::analytics::record(sample, outcome == Dispatched::Forwarded).await;
+    }
+    outcome
+}
 
-            if obj.get("isStreamer").and_then(|v| v.as_bool()).is_none() {
-                obj.insert("isStreamer".to_string(), serde_json::Value::Bool(false));
-            }
+async fn filter_and_forward(connection_id: &str, mut event: LiveEvent, replay: Option<&mut Deduper>) -> Dispatched {
+    // 先补全来源/粉丝牌/主播标记，规则条件才能用到
+    annotate_event(connection_id, &mut event).await;
 
-            if obj.get("hasOwnMedal").and_then(|v| v.as_bool()).is_none() {
-                obj.insert("hasOwnMedal".to_string(), serde_json::Value::Bool(false));
-            }
+    let is_replay = replay.is_some();
+    let filter = DANMU_FILTER.read().await.clone();
+    let (effects, drop) = evaluate_filter(&filter, connection_id, &mut event, replay).await;
+    if let Some(drop) = drop {
+        if ws_debug_enabled() {
+            eprintln!("[Filter][DEBUG] drop: {}", drop.detail);
+        }
+        count_dropped(drop.reason).await;
+        return Dispatched::Dropped(drop.reason);
+    }
+    if !INTERACT_EVENTS.read().await.allows(&event) {
+        return Dispatched::Ignored;
+    }
+    forward_to_sse(connection_id, event, effects, is_replay).await;
+    Dispatched::Forwarded
+}
 
-            if obj.get("guardLevel").and_then(|v| v.as_i64()).is_none() {
-                obj.insert("guardLevel".to_string(), serde_json::Value::Number(0.into()));
-            }
+/// 规则 + 弹幕过滤条件，返回规则效果与丢弃原因（None 为放行）
+async fn evaluate_filter(
+    filter: &ActiveFilter,
+    connection_id: &str,
+    event: &mut LiveEvent,
+    local_dedup: Option<&mut Deduper>,
+) -> (RuleEffects, Option<FilterDrop>) {
+    let effects = filter.rules.apply(event);
+    if let Some(rule) = &effects.dropped_by {
+        let drop = FilterDrop::new(
+            DropReason::Rule,
+            format!("rule={:?} type={} text={:?}", rule, event.kind(), event.common().text),
+        );
+        return (effects, Some(drop));
+    }
+    let drop = match event {
+        LiveEvent::Danmu(_) => danmu_drop_reason(filter, connection_id, event, effects.allowed, local_dedup).await,
+        _ => None,
+    };
+    (effects, drop)
+}
 
-            // 通过 media_ruid 与主播 uid 匹配判断“佩戴本房间粉丝牌”
-            let media_ruid = obj.get("media_ruid").and_then(|v| {
-                if let Some(u) = v.as_u64() {
-                    Some(u.to_string())
-                } else {
-                    v.as_str().map(|s| s.to_string())
-                }
-            });
+/// dry-run 单条结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterEvaluation {
+    pub index: usize,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub user: String,
+    pub uid: Option<u64>,
+    // rewrite 规则生效后的文本
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_text: Option<String>,
+    pub dropped: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<DropReason>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub allowed: bool,
+    pub tags: Vec<String>,
+    pub style: crate::filter_rules::StyleOverride,
+}
 
-            let (owner_uid, owner_face) = {
-                let guard = WS_STATE.read().await;
-                (guard.room_owner_uid, guard.room_owner_face_url.clone())
-            };
+/// 用候选配置评估一批事件，不影响生效中的配置、去重窗口与统计。
+/// connection_id 指定时按该连接的房间信息补全（粉丝牌/主播判断），否则只用事件自带的字段
+pub async fn dry_run_filter(
+    cfg: DanmuFilterConfig,
+    events: Vec<LiveEvent>,
+    connection_id: Option<&str>,
+) -> Result<Vec<FilterEvaluation>, AppError> {
+    let filter = ActiveFilter::compile(cfg)?;
+    let mut deduper = Deduper::default();
+    let mut results = Vec::with_capacity(events.len());
+    for (index, mut event) in events.into_iter().enumerate() {
+        let original = event.common().text.clone();
+        if let Some(cid) = connection_id {
+            annotate_event(cid, &mut event).await;
+        }
+        let (effects, drop) = evaluate_filter(&filter, connection_id.unwrap_or(""), &mut event, Some(&mut deduper)).await;
+        let common = event.common();
+        results.push(FilterEvaluation {
+            index,
+            kind: event.kind(),
+            user: common.user.name.clone(),
+            uid: common.user.uid,
+            text: common.text.clone(),
+            original_text: (common.text != original).then_some(original),
+            dropped: drop.is_some(),
+            reason: drop.as_ref().map(|d| d.reason),
+            detail: drop.map(|d| d.detail),
+            allowed: effects.allowed,
+            tags: effects.tags,
+            style: effects.style,
+        });
+    }
+    Ok(results)
+}
 
-            if let Some(media_ruid) = media_ruid {
-                if let Some(owner_uid) = owner_uid {
-                    if media_ruid == owner_uid.to_string() {
-                        obj.insert("hasOwnMedal".to_string(), serde_json::Value::Bool(true));
-                    }
-                }
-            }
+async fn count_dropped(reason: DropReason) {
+    if let Some(state) = crate::get_sse_state().await {
+        state.stats.write().await.dropped.add(reason);
+    }
+}
 
-            // 主播/本人弹幕：优先用 uid 匹配，其次回退 face_url 匹配（免登录房间直连场景）
-            // 不过目前没设计登录，所以理论上 uid 匹配是没用的。
-            let mut is_streamer = false;
-            if let Some(owner_uid) = owner_uid {
-                if let Some(uid) = obj.get("uid").and_then(|v| v.as_i64()) {
-                    if uid == owner_uid {
-                        is_streamer = true;
-                    }
-                }
+async fn annotate_event(connection_id: &str, event: &mut LiveEvent) {
+    let (room_id, owner_uid, owner_face) = {
+        let conns = WS_CONNECTIONS.read().await;
+        match conns.get(connection_id) {
+            Some(st) => (st.room_id, st.room_owner_uid, st.room_owner_face_url.clone()),
+            std::option::Option::None => (None, None, None),
+        }
+    };
+
+    let common = event.common_mut();
+
+    // 来源标记：多房间同时连接时，前端据此区分事件来自哪个房间
+    common.source.connection_id = Some(connection_id.to_string());
+    common.source.room_id = room_id;
+
+    // 通过 media_ruid 与主播 uid 匹配判断“佩戴本房间粉丝牌”
+    if let (Some(media_ruid), Some(owner_uid)) = (common.medal.media_ruid, owner_uid) {
+        if i64::try_from(media_ruid).ok() == Some(owner_uid) {
+            common.medal.has_own_medal = true;
+        }
+    }
+
+    // 主播/本人弹幕：优先用 uid 匹配，其次回退 face_url 匹配（免登录房间直连场景）
+    // 不过目前没设计登录，所以理论上 uid 匹配是没用的。
+    let mut is_streamer = false;
+    if let (Some(owner_uid), Some(uid)) = (owner_uid, common.user.uid) {
+        if i64::try_from(uid).ok() == Some(owner_uid) {
+            is_streamer = true;
+        }
+    }
+    if !is_streamer {
+        if let (Some(face_url), Some(owner_face)) = (common.user.face_url.as_deref(), owner_face.as_deref()) {
+            if !owner_face.is_empty() && face_url == owner_face {
+                is_streamer = true;
             }
-            if !is_streamer {
-                let face_url = obj.get("face_url").and_then(|v| v.as_str());
-                if let (Some(face_url), Some(owner_face)) = (face_url, owner_face.as_deref()) {
-                    if !owner_face.is_empty() && face_url == owner_face {
-                        is_streamer = true;
-                    }
-                }
+        }
+    }
+    if is_streamer {
+        common.user.is_streamer = true;
+    }
+}
+
+/// replay：回放的是已经记录过的事件，只推送，不再入库也不计入排行榜
+async fn forward_to_sse(connection_id: &str, mut event: LiveEvent, effects: RuleEffects, replay: bool) {
+    if let Some(state) = crate::get_sse_state().await {
+        // 醒目留言删除：不推送字幕，而是让预览端撤回对应的 SC
+        if let LiveEvent::SuperChatDelete(del) = &event {
+            let ids = del
+                .sc_ids
+                .iter()
+                .map(|sc_id| super_chat_event_id(connection_id, *sc_id))
+                .collect::<Vec<_>>();
+            if !ids.is_empty() {
+                crate::sse_server::broadcast_retract(&state, ids).await;
             }
-            if is_streamer {
-                obj.insert("isStreamer".to_string(), serde_json::Value::Bool(true));
+            return;
+        }
+
+        // 醒目留言 id 由 scId 推出，之后收到删除通知时才能对上
+        if let LiveEvent::SuperChat(sc) = &mut event {
+            if let Some(sc_id) = sc.sc_id {
+                sc.common.id = super_chat_event_id(connection_id, sc_id);
             }
         }
 
-        let val = crate::apply_style_to_sse_message(val).await;
+        if !replay {
+            crate::history::record_event(&event);
+            crate::leaderboard::record_event(&event).await;
+        }
+
+        let mut val = crate::apply_style_to_sse_message(event).await;
+        effects.apply_to(&mut val);
         crate::sse_server::send_to_all_connections(&state, val).await;
     }
 }
 
-pub async fn current_room_key() -> Option<String> {
-    WS_STATE.read().await.current_room_key.clone()
+async fn emit_status(connection_id: &str, status: &str, message: &str) {
+    emit_status_with_code(connection_id, status, None, message).await;
 }
 
-pub async fn current_room_key_type() -> Option<String> {
-    WS_STATE
-        .read()
-        .await
-        .current_room_key_type
-        .map(|t| match t {
-            RoomKeyType::RoomId => "RoomId".to_string(),
-            RoomKeyType::AuthCode => "AuthCode".to_string(),
-        })
-}
-
-async fn emit_status(status: &str, message: &str) {
-    let guard = WS_STATE.read().await;
-    if let Some(app_handle) = &guard.app_handle {
+/// code 供前端按类型分支（例如 TOKEN_INVALID 时提示检查房间号），message 仅用于展示
+pub(crate) async fn emit_status_with_code(connection_id: &str, status: &str, code: Option<&str>, message: &str) {
+    let app_handle = APP_HANDLE.read().await;
+    if let Some(app_handle) = app_handle.as_ref() {
+        let room_key = WS_CONNECTIONS
+            .read()
+            .await
+            .get(connection_id)
+            .map(|st| st.room_key.clone());
         let _ = app_handle.emit("websocket-status", serde_json::json!({
+            "connectionId": connection_id,
+            "roomKey": room_key,
             "status": status,
+            "code": code,
             "message": message
         }));
     }
@@ -1281,18 +2578,64 @@ async fn emit_status(status: &str, message: &str) {
 
 /* ---------------- 开放平台 REST ---------------- */
 
-#[derive(Deserialize)]
-struct StartGameResp {
-    data: StartGameData,
+const OPEN_LIVE_API: &str = "https://live-open.biliapi.com";
+// blivedm/clients/open_live.py：项目心跳 20s 一次，超过 60s 未收到心跳会自动关闭
+const OPEN_LIVE_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(20);
+
+#[derive(Clone, Debug)]
+struct OpenLiveCredentials {
+    app_id: i64,
+    access_key_id: String,
+    access_key_secret: String,
 }
+
+impl OpenLiveCredentials {
+    /// 参数为空时回退读取环境变量
+    fn resolve(
+        open_live_app_id: Option<i64>,
+        open_live_access_key_id: Option<String>,
+        open_live_access_key_secret: Option<String>,
+    ) -> Result<Self, AppError> {
+        let missing = |name: &str| AppError::MissingCredentials(format!("缺少 {name}"));
+        let app_id: i64 = match open_live_app_id {
+            Some(v) => v,
+            std::option::Option::None => std::env::var("BILI_OPEN_LIVE_APP_ID")
+                .map_err(|_| missing("BILI_OPEN_LIVE_APP_ID"))?
+                .parse()
+                .map_err(|_| AppError::MissingCredentials("BILI_OPEN_LIVE_APP_ID 不是数字".to_string()))?,
+        };
+        let access_key_id = match open_live_access_key_id {
+            Some(v) => v,
+            std::option::Option::None => std::env::var("BILI_OPEN_LIVE_ACCESS_KEY_ID")
+                .map_err(|_| missing("BILI_OPEN_LIVE_ACCESS_KEY_ID"))?,
+        };
+        let access_key_secret = match open_live_access_key_secret {
+            Some(v) => v,
+            std::option::Option::None => std::env::var("BILI_OPEN_LIVE_ACCESS_KEY_SECRET")
+                .map_err(|_| missing("BILI_OPEN_LIVE_ACCESS_KEY_SECRET"))?,
+        };
+        Ok(Self {
+            app_id,
+            access_key_id,
+            access_key_secret,
+        })
+    }
+}
+
+/// 一次开放平台玩法会话：app/heartbeat 与 app/end 都需要 game_id
+#[derive(Clone, Debug)]
+struct OpenLiveGame {
+    game_id: String,
+    credentials: OpenLiveCredentials,
+}
+
 #[derive(Deserialize)]
-#[allow(dead_code)]
 struct StartGameData {
     game_info: GameInfo,
     websocket_info: WebsocketInfo,
+    anchor_info: Option<AnchorInfo>,
 }
 #[derive(Deserialize)]
-#[allow(dead_code)]
 struct GameInfo {
     game_id: String,
 }
@@ -1301,44 +2644,25 @@ struct WebsocketInfo {
     wss_link: Vec<String>,
     auth_body: String,
 }
+#[derive(Deserialize)]
+struct AnchorInfo {
+    room_id: i64,
+}
 
 struct WsConnInfo {
     ws_url: String,
     auth_body: String,
+    room_id: Option<i64>,
+    game: OpenLiveGame,
 }
 
-async fn start_game_and_get_ws(
-    auth_code: &str,
-    open_live_app_id: Option<i64>,
-    open_live_access_key_id: Option<String>,
-    open_live_access_key_secret: Option<String>,
-) -> Result<WsConnInfo, String> {
+/// 带签名请求开放平台接口，返回 data 字段（code != 0 视为失败）
+async fn open_live_request(
+    credentials: &OpenLiveCredentials,
+    path: &str,
+    body_json: String,
+) -> Result<serde_json::Value, AppError> {
     // 依照 blivedm/clients/open_live.py _request_open_live
-    let app_id: i64 = match open_live_app_id {
-        Some(v) => v,
-        std::option::Option::None => std::env::var("BILI_OPEN_LIVE_APP_ID")
-            .map_err(|_| "缺少 BILI_OPEN_LIVE_APP_ID")?
-            .parse()
-            .map_err(|_| "BILI_OPEN_LIVE_APP_ID 不是数字")?,
-    };
-    let access_key_id = match open_live_access_key_id {
-        Some(v) => v,
-        std::option::Option::None => std::env::var("BILI_OPEN_LIVE_ACCESS_KEY_ID")
-            .map_err(|_| "缺少 BILI_OPEN_LIVE_ACCESS_KEY_ID")?,
-    };
-    let access_key_secret = match open_live_access_key_secret {
-        Some(v) => v,
-        std::option::Option::None => std::env::var("BILI_OPEN_LIVE_ACCESS_KEY_SECRET")
-            .map_err(|_| "缺少 BILI_OPEN_LIVE_ACCESS_KEY_SECRET")?,
-    };
-
-    #[derive(Serialize)]
-    struct Body<'a> {
-        code: &'a str,
-        app_id: i64,
-    }
-    let body = Body { code: auth_code, app_id };
-    let body_json = serde_json::to_string(&body).unwrap();
     let body_bytes = body_json.as_bytes();
 
     // 构造头部并签名（与 Python 版一致）
@@ -1349,7 +2673,7 @@ async fn start_game_and_get_ws(
 
     // 注意顺序固定
     let str_to_sign = [
-        ("x-bili-accesskeyid", access_key_id.as_str()),
+        ("x-bili-accesskeyid", credentials.access_key_id.as_str()),
         ("x-bili-content-md5", content_md5.as_str()),
         ("x-bili-signature-method", "HMAC-SHA256"),
         ("x-bili-signature-nonce", signature_nonce.as_str()),
@@ -1361,13 +2685,13 @@ async fn start_game_and_get_ws(
     .collect::<Vec<_>>()
     .join("\n");
 
-    let signature = hmac_sha256::HMAC::mac(str_to_sign.as_bytes(), access_key_secret.as_bytes());
+    let signature = hmac_sha256::HMAC::mac(str_to_sign.as_bytes(), credentials.access_key_secret.as_bytes());
     let signature_hex = hex::encode(signature);
 
     let client = reqwest::Client::new();
     let res = client
-        .post("https://live-open.biliapi.com/v2/app/start")
-        .header("x-bili-accesskeyid", &access_key_id)
+        .post(format!("{OPEN_LIVE_API}{path}"))
+        .header("x-bili-accesskeyid", &credentials.access_key_id)
         .header("x-bili-content-md5", &content_md5)
         .header("x-bili-signature-method", "HMAC-SHA256")
         .header("x-bili-signature-nonce", &signature_nonce)
@@ -1379,26 +2703,108 @@ async fn start_game_and_get_ws(
         .body(body_json)
         .send()
         .await
-        .map_err(|e| format!("start 请求失败: {e}"))?;
+        .map_err(|e| AppError::Network(format!("{path} 请求失败: {e}")))?;
     if !res.status().is_success() {
-        return Err(format!("start 返回状态码 {}", res.status()));
+        return Err(AppError::Network(format!("{path} 返回状态码 {}", res.status())));
+    }
+    let parsed: serde_json::Value = res
+        .json()
+        .await
+        .map_err(|e| AppError::BadResponse(format!("{path} 解析失败: {e}")))?;
+    let code = parsed.get("code").and_then(|v| v.as_i64()).unwrap_or(0);
+    if code != 0 {
+        return Err(AppError::Upstream {
+            api: path.to_string(),
+            code,
+            message: parsed.get("message").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+        });
     }
-    let parsed: StartGameResp = res.json().await.map_err(|e| format!("start 解析失败: {e}"))?;
+    Ok(parsed.get("data").cloned().unwrap_or(serde_json::Value::Null))
+}
+
+async fn start_game_and_get_ws(auth_code: &str, credentials: OpenLiveCredentials) -> Result<WsConnInfo, AppError> {
+    #[derive(Serialize)]
+    struct Body<'a> {
+        code: &'a str,
+        app_id: i64,
+    }
+    let body = Body { code: auth_code, app_id: credentials.app_id };
+    let body_json = serde_json::to_string(&body).unwrap();
+
+    let data = open_live_request(&credentials, "/v2/app/start", body_json).await?;
+    let parsed: StartGameData =
+        serde_json::from_value(data).map_err(|e| AppError::BadResponse(format!("start 解析失败: {e}")))?;
     let ws_url = parsed
-        .data
         .websocket_info
         .wss_link
-        .get(0)
+        .first()
         .cloned()
-        .ok_or_else(|| "start 返回的 wss_link 为空".to_string())?;
+        .ok_or_else(|| AppError::BadResponse("start 返回的 wss_link 为空".to_string()))?;
+    if ws_debug_enabled() {
+        eprintln!("[WebSocket][DEBUG] app/start game_id={}", parsed.game_info.game_id);
+    }
     Ok(WsConnInfo {
         ws_url,
-        auth_body: parsed.data.websocket_info.auth_body,
+        auth_body: parsed.websocket_info.auth_body,
+        room_id: parsed.anchor_info.map(|a| a.room_id),
+        game: OpenLiveGame {
+            game_id: parsed.game_info.game_id,
+            credentials,
+        },
     })
 }
 
+async fn run_open_live_heartbeat(connection_id: String, game: OpenLiveGame) {
+    let mut interval = tokio::time::interval(OPEN_LIVE_HEARTBEAT_INTERVAL);
+    // 第一次 tick 立即返回，app/start 刚成功时不需要马上心跳
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let body_json = serde_json::json!({ "game_id": game.game_id }).to_string();
+        match open_live_request(&game.credentials, "/v2/app/heartbeat", body_json).await {
+            Ok(_) => {
+                if ws_debug_enabled() {
+                    eprintln!("[WebSocket][DEBUG][{connection_id}] app/heartbeat ok game_id={}", game.game_id);
+                }
+            }
+            Err(e) => {
+                eprintln!("[WebSocket][{connection_id}] 开放平台心跳失败: {e}");
+                emit_status_with_code(&connection_id, "warning", Some(e.code()), &format!("开放平台心跳失败: {e}")).await;
+            }
+        }
+    }
+}
+
+/// 停止 app/heartbeat 并调用 app/end（没有进行中的玩法会话时什么也不做）
+async fn end_open_live_game(connection_id: &str) {
+    let (game, heartbeat) = {
+        let mut conns = WS_CONNECTIONS.write().await;
+        match conns.get_mut(connection_id) {
+            Some(st) => (st.open_live_game.take(), st.open_live_heartbeat.take()),
+            std::option::Option::None => (None, None),
+        }
+    };
+    if let Some(h) = heartbeat {
+        h.abort();
+    }
+    let Some(game) = game else {
+        return;
+    };
+
+    let body_json = serde_json::json!({
+        "game_id": game.game_id,
+        "app_id": game.credentials.app_id,
+    })
+    .to_string();
+    match open_live_request(&game.credentials, "/v2/app/end", body_json).await {
+        Ok(_) => println!("[WebSocket][{connection_id}] 开放平台玩法已结束 game_id={}", game.game_id),
+        Err(e) => eprintln!("[WebSocket][{connection_id}] app/end 失败: {e}"),
+    }
+}
+
 /// CLI/测试场景使用：不依赖 Tauri AppHandle，也不发送前端事件。
 pub async fn connect_websocket_cli(
+    connection_id: String,
     room_key: String,
     room_key_type: RoomKeyType,
     reconnect_interval: u64,
@@ -1406,34 +2812,86 @@ pub async fn connect_websocket_cli(
     open_live_app_id: Option<i64>,
     open_live_access_key_id: Option<String>,
     open_live_access_key_secret: Option<String>,
-) -> Result<(), String> {
-    {
-        let guard = WS_STATE.read().await;
-        if guard.handle.is_some() {
-            return Err("已有连接".to_string());
-        }
-    }
-
-    {
-        let mut guard = WS_STATE.write().await;
-        guard.app_handle = None;
-        guard.should_stop = false;
-    }
-
-    let handle = tokio::spawn(run_ws_loop(
-        room_key,
+) -> Result<(), AppError> {
+    *APP_HANDLE.write().await = None;
+    let source = build_source(
+        &room_key,
         room_key_type,
         reconnect_interval,
         max_reconnect_attempts,
         open_live_app_id,
         open_live_access_key_id,
         open_live_access_key_secret,
-    ));
+    );
+    spawn_connection(connection_id, room_key, room_key_type, source).await
+}
 
-    {
-        let mut guard = WS_STATE.write().await;
-        guard.handle = Some(handle);
+/// 回放录制文件（packet_log 或事件日志），speed 为倍速；断开同样使用 disconnect_websocket
+pub async fn connect_replay(
+    app_handle: AppHandle,
+    connection_id: String,
+    path: String,
+    speed: f64,
+) -> Result<(), AppError> {
+    *APP_HANDLE.write().await = Some(app_handle);
+    spawn_replay(connection_id, path, speed).await
+}
+
+/// CLI/回归测试使用：不依赖 Tauri AppHandle
+pub async fn connect_replay_cli(connection_id: String, path: String, speed: f64) -> Result<(), AppError> {
+    *APP_HANDLE.write().await = None;
+    spawn_replay(connection_id, path, speed).await
+}
+
+async fn spawn_replay(connection_id: String, path: String, speed: f64) -> Result<(), AppError> {
+    crate::replay_source::validate_replay_speed(speed)?;
+    if !std::path::Path::new(&path).is_file() {
+        return Err(AppError::InvalidArgument(format!("录制文件不存在: {path}")));
     }
+    let source = Arc::new(crate::replay_source::ReplaySource::new(path.clone(), speed));
+    spawn_connection(connection_id, path, RoomKeyType::Replay, source).await
+}
 
-    Ok(())
-}
\ No newline at end of file
+/* ---------------- LiveSource：B 站公屏 / 开放平台 ---------------- */
+
+/// B 站的两种接入方式：RoomId 走公屏弹幕服务器，AuthCode 走开放平台 app/start
+struct BiliLiveSource {
+    room_key: String,
+    room_key_type: RoomKeyType,
+    reconnect_interval: u64,
+    max_reconnect_attempts: u32,
+    open_live_app_id: Option<i64>,
+    open_live_access_key_id: Option<String>,
+    open_live_access_key_secret: Option<String>,
+}
+
+impl LiveSource for BiliLiveSource {
+    fn platform(&self) -> &'static str {
+        match self.room_key_type {
+            RoomKeyType::RoomId => "bilibili",
+            RoomKeyType::AuthCode => "bilibili-open",
+            RoomKeyType::TwitchChannel => "twitch",
+            RoomKeyType::Replay => "replay",
+        }
+    }
+
+    fn connect(self: Arc<Self>, ctx: SourceContext) -> futures::future::BoxFuture<'static, ()> {
+        Box::pin(async move {
+            run_ws_loop(
+                ctx,
+                self.room_key.clone(),
+                self.room_key_type,
+                self.reconnect_interval,
+                self.max_reconnect_attempts,
+                self.open_live_app_id,
+                self.open_live_access_key_id.clone(),
+                self.open_live_access_key_secret.clone(),
+            )
+            .await
+        })
+    }
+
+    fn disconnect(self: Arc<Self>, connection_id: String) -> futures::future::BoxFuture<'static, ()> {
+        Box::pin(async move { end_open_live_game(&connection_id).await })
+    }
+}
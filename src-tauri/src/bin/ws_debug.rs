@@ -4,7 +4,7 @@ use app_lib::bili_websocket_client::{self, RoomKeyType};
 
 fn usage() {
     eprintln!(
-        "Usage:\n  ws_debug roomid <RoomId>\n  ws_debug authcode <AuthCode>\n\nEnv:\n  BILI_WS_DEBUG=1   enable verbose ws logs\n  BILI_OPEN_LIVE_APP_ID / BILI_OPEN_LIVE_ACCESS_KEY_ID / BILI_OPEN_LIVE_ACCESS_KEY_SECRET (for authcode)\n"
+        "Usage:\n  ws_debug roomid <RoomId> [<RoomId> ...]\n  ws_debug authcode <AuthCode>\n  ws_debug replay <File> [<Speed>]   (speed 0: no delay, otherwise 0.1..=100)\n\nEnv:\n  BILI_WS_DEBUG=1   enable verbose ws logs\n  BILI_OPEN_LIVE_APP_ID / BILI_OPEN_LIVE_ACCESS_KEY_ID / BILI_OPEN_LIVE_ACCESS_KEY_SECRET (for authcode)\n"
     );
 }
 
@@ -22,7 +22,12 @@ async fn main() {
     }
 
     let mode = args.remove(0);
-    let key = args.remove(0);
+    let keys = args;
+
+    if mode == "replay" {
+        replay(&keys).await;
+        return;
+    }
 
     let room_key_type = match mode.as_str() {
         "roomid" | "room_id" => RoomKeyType::RoomId,
@@ -34,21 +39,49 @@ async fn main() {
     let reconnect_interval = 3000;
     let max_reconnect_attempts = 2;
 
-    if let Err(e) = bili_websocket_client::connect_websocket_cli(
-        key,
-        room_key_type,
-        reconnect_interval,
-        max_reconnect_attempts,
-        None,
-        None,
-        None,
-    )
-    .await
-    {
-        eprintln!("connect_websocket_cli failed: {e}");
-        std::process::exit(1);
+    // 每个房间各自一条连接，connection_id 直接使用房间号/用户码
+    for key in keys {
+        if let Err(e) = bili_websocket_client::connect_websocket_cli(
+            key.clone(),
+            key,
+            room_key_type,
+            reconnect_interval,
+            max_reconnect_attempts,
+            None,
+            None,
+            None,
+        )
+        .await
+        {
+            eprintln!("connect_websocket_cli failed: {e}");
+            std::process::exit(1);
+        }
     }
 
     tokio::time::sleep(Duration::from_secs(30)).await;
-    let _ = bili_websocket_client::disconnect_websocket().await;
+    bili_websocket_client::disconnect_all().await;
+}
+
+/// 回放录制文件并等待回放结束（SSE 未启动，仅输出解析日志）
+async fn replay(args: &[String]) {
+    let path = args[0].clone();
+    let speed = match args.get(1) {
+        Some(s) => s.parse::<f64>().unwrap_or_else(|_| usage_and_exit(2)),
+        None => 1.0,
+    };
+    if let Err(e) = app_lib::replay_source::validate_replay_speed(speed) {
+        eprintln!("{e}");
+        usage_and_exit(2);
+    }
+
+    if let Err(e) = bili_websocket_client::connect_replay_cli("replay".to_string(), path, speed).await {
+        eprintln!("connect_replay_cli failed: {e}");
+        std::process::exit(1);
+    }
+
+    // 等回放结束且管线处理完最后的事件，再输出统计，便于对比两次回放的过滤结果
+    if let Some(stats) = bili_websocket_client::wait_pipeline("replay").await {
+        println!("{}", serde_json::to_string(&stats).unwrap_or_default());
+    }
+    bili_websocket_client::disconnect_all().await;
 }
@@ -0,0 +1,297 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::bili_websocket_client::DropReason;
+
+// 最多保留的已转发弹幕条数（防止超高频时窗口无限增长）
+const MAX_SEEN: usize = 5000;
+// 近似重复只和最近这么多条比较，编辑距离是 O(n*m)
+const SIMILAR_LOOKBACK: usize = 50;
+// 太短的文本（“草”“哈哈”）相似度没有意义，只做完全重复判断
+const SIMILAR_MIN_CHARS: usize = 3;
+
+/// 重复/刷屏抑制配置（DanmuFilterConfig.dedup）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DedupConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    // 任何人 N 秒内发送相同内容（归一化后）只转发第一条；0 表示关闭
+    #[serde(default = "default_window_secs")]
+    pub window_secs: u64,
+    // 同一用户 N 秒内重复相同内容；0 表示关闭
+    #[serde(default = "default_user_repeat_secs")]
+    pub user_repeat_secs: u64,
+    // 近似重复：与 window_secs 内的弹幕归一化编辑距离相似度 >= 该值即丢弃（0~1）；0 表示关闭
+    #[serde(default = "default_similarity")]
+    pub similarity: f64,
+    // 每个用户 rate_window_secs 秒内最多转发 user_rate_limit 条；0 表示不限
+    #[serde(default)]
+    pub user_rate_limit: u32,
+    #[serde(default = "default_rate_window_secs")]
+    pub rate_window_secs: u64,
+}
+
+fn default_window_secs() -> u64 {
+    10
+}
+
+fn default_user_repeat_secs() -> u64 {
+    60
+}
+
+fn default_similarity() -> f64 {
+    0.8
+}
+
+fn default_rate_window_secs() -> u64 {
+    10
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_secs: default_window_secs(),
+            user_repeat_secs: default_user_repeat_secs(),
+            similarity: default_similarity(),
+            user_rate_limit: 0,
+            rate_window_secs: default_rate_window_secs(),
+        }
+    }
+}
+
+// 一条已转发的弹幕；只记录放行的，被丢弃的不参与后续判断（“只转发第一条”）
+struct Seen {
+    time: u64,
+    user: String,
+    text: Vec<char>,
+}
+
+/// 去重窗口；直播时用全局的一份，dry-run 与回放各自新建（按事件时间戳）
+#[derive(Default)]
+pub struct Deduper {
+    seen: VecDeque<Seen>,
+}
+
+static DEDUPER: Lazy<Arc<RwLock<Deduper>>> = Lazy::new(|| Arc::new(RwLock::new(Deduper::default())));
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// 编辑距离相似度：1 - distance / max_len
+fn similarity(a: &[char], b: &[char]) -> f64 {
+    let max_len = a.len().max(b.len());
+    if max_len == 0 {
+        return 1.0;
+    }
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        cur[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != cb);
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    1.0 - prev[b.len()] as f64 / max_len as f64
+}
+
+/// 判断一条弹幕是否为重复/刷屏（全局窗口，按当前时间）
+pub async fn check(cfg: &DedupConfig, user: &str, text: &str) -> Option<DropReason> {
+    if !cfg.enabled {
+        return None;
+    }
+    DEDUPER.write().await.check(cfg, user, text, now_ms())
+}
+
+impl Deduper {
+    /// 判断一条弹幕是否为重复/刷屏；放行时记入窗口。user 为用户标识（uid 或用户名），now 为 Unix 毫秒
+    pub fn check(&mut self, cfg: &DedupConfig, user: &str, text: &str, now: u64) -> Option<DropReason> {
+        if !cfg.enabled {
+            return None;
+        }
+
+        // 归一化后比较：空格/标点/全半角/繁简差异不算“不同”
+        let normalized = crate::keyword_filter::normalize(text);
+        let text = if normalized.is_empty() { text.trim().to_string() } else { normalized };
+        let chars = text.chars().collect::<Vec<_>>();
+
+        let keep_ms = cfg
+            .window_secs
+            .max(cfg.user_repeat_secs)
+            .max(if cfg.user_rate_limit > 0 { cfg.rate_window_secs } else { 0 })
+            .saturating_mul(1000);
+        let within = |seen: &Seen, secs: u64| secs > 0 && now.saturating_sub(seen.time) < secs * 1000;
+
+        let seen = &mut self.seen;
+        while seen.front().is_some_and(|s| now.saturating_sub(s.time) >= keep_ms) {
+            seen.pop_front();
+        }
+
+        if cfg.user_rate_limit > 0 {
+            let count = seen
+                .iter()
+                .filter(|s| s.user == user && within(s, cfg.rate_window_secs))
+                .count();
+            if count >= cfg.user_rate_limit as usize {
+                return Some(DropReason::RateLimited);
+            }
+        }
+        if seen
+            .iter()
+            .any(|s| s.user == user && s.text == chars && within(s, cfg.user_repeat_secs))
+        {
+            return Some(DropReason::UserRepeat);
+        }
+        if seen.iter().any(|s| s.text == chars && within(s, cfg.window_secs)) {
+            return Some(DropReason::Duplicate);
+        }
+        if cfg.similarity > 0.0 && chars.len() >= SIMILAR_MIN_CHARS {
+            let similar = seen
+                .iter()
+                .rev()
+                .take(SIMILAR_LOOKBACK)
+                .filter(|s| within(s, cfg.window_secs) && s.text.len() >= SIMILAR_MIN_CHARS)
+                // 仅长度差就已低于阈值时不用算（与 similarity 同一算法，阈值边界上结果一致）
+                .filter(|s| {
+                    let max_len = s.text.len().max(chars.len()) as f64;
+                    1.0 - s.text.len().abs_diff(chars.len()) as f64 / max_len >= cfg.similarity
+                })
+                .any(|s| similarity(&s.text, &chars) >= cfg.similarity);
+            if similar {
+                return Some(DropReason::Similar);
+            }
+        }
+
+        seen.push_back(Seen {
+            time: now,
+            user: user.to_string(),
+            text: chars,
+        });
+        if seen.len() > MAX_SEEN {
+            seen.pop_front();
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sse_server::DroppedStats;
+
+    fn cfg() -> DedupConfig {
+        DedupConfig {
+            enabled: true,
+            ..DedupConfig::default()
+        }
+    }
+
+    #[test]
+    fn drops_exact_duplicates_until_window_expires() {
+        let cfg = cfg();
+        let mut d = Deduper::default();
+        assert_eq!(d.check(&cfg, "uid:1", "主播晚上好", 0), None);
+        // 标点、全半角不同也算同一内容
+        assert_eq!(d.check(&cfg, "uid:2", "主播晚上好！！", 5_000), Some(DropReason::Duplicate));
+        // window_secs 过后其他人可以再发
+        assert_eq!(d.check(&cfg, "uid:3", "主播晚上好", 10_000), None);
+    }
+
+    #[test]
+    fn drops_same_user_repeats_for_longer_window() {
+        let cfg = cfg();
+        let mut d = Deduper::default();
+        assert_eq!(d.check(&cfg, "uid:1", "打卡", 0), None);
+        assert_eq!(d.check(&cfg, "uid:1", "打卡", 30_000), Some(DropReason::UserRepeat));
+        assert_eq!(d.check(&cfg, "uid:1", "打卡", 60_000), None);
+    }
+
+    #[test]
+    fn near_duplicates_follow_similarity_threshold() {
+        let cfg = cfg();
+        let mut d = Deduper::default();
+        assert_eq!(d.check(&cfg, "uid:1", "主播好帅啊", 0), None);
+        // 5 字改 1 字：相似度正好 0.8
+        assert_eq!(d.check(&cfg, "uid:2", "主播好帅呀", 1_000), Some(DropReason::Similar));
+        // 改 2 字：0.6，低于阈值
+        assert_eq!(d.check(&cfg, "uid:3", "主播真帅呀", 2_000), None);
+
+        let strict = DedupConfig {
+            similarity: 0.9,
+            ..cfg.clone()
+        };
+        let mut d = Deduper::default();
+        assert_eq!(d.check(&strict, "uid:1", "主播好帅啊", 0), None);
+        assert_eq!(d.check(&strict, "uid:2", "主播好帅呀", 1_000), None);
+
+        // 太短的文本不做近似判断
+        let mut d = Deduper::default();
+        assert_eq!(d.check(&cfg, "uid:1", "好耶", 0), None);
+        assert_eq!(d.check(&cfg, "uid:2", "好呀", 1_000), None);
+    }
+
+    #[test]
+    fn limits_messages_per_user() {
+        let cfg = DedupConfig {
+            user_rate_limit: 2,
+            ..cfg()
+        };
+        let mut d = Deduper::default();
+        assert_eq!(d.check(&cfg, "uid:1", "第一条", 0), None);
+        assert_eq!(d.check(&cfg, "uid:1", "第二句话", 1_000), None);
+        assert_eq!(d.check(&cfg, "uid:1", "再来一个", 2_000), Some(DropReason::RateLimited));
+        // 其他用户不受影响
+        assert_eq!(d.check(&cfg, "uid:2", "再来一个", 2_000), None);
+        // 窗口滑过第一条后恢复
+        assert_eq!(d.check(&cfg, "uid:1", "终于能发了", 10_000), None);
+    }
+
+    #[test]
+    fn disabled_config_passes_everything() {
+        let cfg = DedupConfig::default();
+        let mut d = Deduper::default();
+        assert_eq!(d.check(&cfg, "uid:1", "刷屏", 0), None);
+        assert_eq!(d.check(&cfg, "uid:1", "刷屏", 0), None);
+    }
+
+    #[test]
+    fn counts_drops_by_reason() {
+        let cfg = DedupConfig {
+            user_rate_limit: 3,
+            ..cfg()
+        };
+        let mut d = Deduper::default();
+        let mut stats = DroppedStats::default();
+        let stream = [
+            ("uid:1", "前排前排", 0),
+            ("uid:2", "前排前排", 100),
+            ("uid:3", "前排前排吧", 200),
+            ("uid:1", "前排前排", 12_000),
+            ("uid:1", "第二条", 12_100),
+            ("uid:1", "第三条", 12_200),
+            ("uid:1", "第四条", 12_300),
+            ("uid:1", "第五条", 12_400),
+        ];
+        for (user, text, now) in stream {
+            if let Some(reason) = d.check(&cfg, user, text, now) {
+                stats.add(reason);
+            }
+        }
+        assert_eq!(stats.total, 4);
+        assert_eq!(stats.duplicate, 1);
+        assert_eq!(stats.user_repeat, 1);
+        assert_eq!(stats.similar, 1);
+        assert_eq!(stats.rate_limited, 1);
+        assert_eq!(stats.filtered, 0);
+    }
+}
@@ -0,0 +1,144 @@
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+/// 错误大类：前端/HTTP 调用方按此决定提示方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorCategory {
+    Network,
+    Auth,
+    Config,
+    Io,
+}
+
+/// 后端统一错误：Tauri 命令与 SSE HTTP 接口都返回它。
+/// 序列化为 {"code","category","retryable","message"}，调用方按 code 分支，message 仅用于展示。
+#[derive(Debug, Clone)]
+pub enum AppError {
+    // SSE 服务未启动
+    SseNotRunning,
+    // SSE 端口绑定失败（端口被占用等）
+    SseBindFailed(String),
+    // 请求失败 / 非 2xx 状态码 / 连接失败
+    Network(String),
+    // 上游返回内容无法解析或缺字段
+    BadResponse(String),
+    // 上游接口返回非 0 code（api 为接口名，如 getDanmuInfo、/v2/app/start）
+    Upstream { api: String, code: i64, message: String },
+    // IP 被风控（HTTP 412 / -352 / -412）
+    RiskControl(String),
+    // SSE 接口 token 不匹配
+    Unauthorized,
+    // 开放平台凭据缺失或格式错误
+    MissingCredentials(String),
+    // 参数错误
+    InvalidArgument(String),
+    // 连接 id 已存在 / 不存在
+    ConnectionExists(String),
+    ConnectionNotFound(String),
+    // 读写配置文件、窗口操作、打开浏览器等本地错误
+    Io(String),
+    // 历史记录库（SQLite）打开/读写失败
+    Database(String),
+}
+
+impl AppError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::SseNotRunning => "SSE_NOT_RUNNING",
+            AppError::SseBindFailed(_) => "SSE_BIND_FAILED",
+            AppError::Network(_) => "NETWORK",
+            AppError::BadResponse(_) => "BAD_RESPONSE",
+            AppError::Upstream { .. } => "UPSTREAM",
+            AppError::RiskControl(_) => "RISK_CONTROL",
+            AppError::Unauthorized => "UNAUTHORIZED",
+            AppError::MissingCredentials(_) => "MISSING_CREDENTIALS",
+            AppError::InvalidArgument(_) => "INVALID_ARGUMENT",
+            AppError::ConnectionExists(_) => "CONNECTION_EXISTS",
+            AppError::ConnectionNotFound(_) => "CONNECTION_NOT_FOUND",
+            AppError::Io(_) => "IO",
+            AppError::Database(_) => "DATABASE",
+        }
+    }
+
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            AppError::Network(_) | AppError::BadResponse(_) | AppError::RiskControl(_) => ErrorCategory::Network,
+            AppError::Upstream { .. } | AppError::Unauthorized | AppError::MissingCredentials(_) => ErrorCategory::Auth,
+            AppError::SseNotRunning
+            | AppError::InvalidArgument(_)
+            | AppError::ConnectionExists(_)
+            | AppError::ConnectionNotFound(_) => ErrorCategory::Config,
+            AppError::SseBindFailed(_) | AppError::Io(_) | AppError::Database(_) => ErrorCategory::Io,
+        }
+    }
+
+    /// 不改任何设置、稍后原样重试是否可能成功
+    pub fn retryable(&self) -> bool {
+        matches!(
+            self,
+            AppError::Network(_) | AppError::BadResponse(_) | AppError::RiskControl(_) | AppError::Io(_)
+        )
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            AppError::SseNotRunning => "SSE服务器未启动".to_string(),
+            AppError::Unauthorized => "Unauthorized".to_string(),
+            AppError::Upstream { api, code, message } => format!("{api} code={code} message={message}"),
+            AppError::SseBindFailed(m)
+            | AppError::Network(m)
+            | AppError::BadResponse(m)
+            | AppError::RiskControl(m)
+            | AppError::MissingCredentials(m)
+            | AppError::InvalidArgument(m)
+            | AppError::ConnectionExists(m)
+            | AppError::ConnectionNotFound(m)
+            | AppError::Io(m)
+            | AppError::Database(m) => m.clone(),
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::InvalidArgument(_) => StatusCode::BAD_REQUEST,
+            AppError::ConnectionNotFound(_) => StatusCode::NOT_FOUND,
+            AppError::ConnectionExists(_) => StatusCode::CONFLICT,
+            AppError::SseNotRunning => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Network(_) | AppError::BadResponse(_) | AppError::Upstream { .. } | AppError::RiskControl(_) => {
+                StatusCode::BAD_GATEWAY
+            }
+            AppError::SseBindFailed(_) | AppError::MissingCredentials(_) | AppError::Io(_) | AppError::Database(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message())
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("AppError", 4)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("category", &self.category())?;
+        s.serialize_field("retryable", &self.retryable())?;
+        s.serialize_field("message", &self.message())?;
+        s.end()
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        (self.status_code(), axum::Json(self)).into_response()
+    }
+}
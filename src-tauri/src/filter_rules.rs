@@ -0,0 +1,338 @@
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::live_event::LiveEvent;
+
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+// rewrite 的 {text} {user} 占位符；一次替换，弹幕里原样出现的 “{user}” 不会被再次展开
+static PLACEHOLDER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{(text|user)\}").unwrap());
+
+/// 过滤规则：按顺序匹配，条件全部满足（AND）时执行动作。
+/// drop / allow 命中后不再看后面的规则；restyle / rewrite / tag 可以叠加。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterRule {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub conditions: RuleConditions,
+    pub action: RuleAction,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// 规则条件：未填写的条件不参与判断；全部为空时匹配所有事件
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RuleConditions {
+    // 事件类型（danmu / gift / superChat / guardBuy / enter ...）
+    pub types: Vec<String>,
+    // 舰队等级：1 总督 / 2 提督 / 3 舰长 / 0 非舰队
+    pub guard_levels: Vec<u8>,
+    // 是否佩戴本房间粉丝牌
+    pub has_own_medal: Option<bool>,
+    pub min_medal_level: Option<u32>,
+    pub medal_name: Option<String>,
+    pub is_streamer: Option<bool>,
+    pub is_moderator: Option<bool>,
+    // 文本正则（不区分大小写）
+    pub text_regex: Option<String>,
+    // 文本长度（字符数，闭区间）
+    pub min_len: Option<u32>,
+    pub max_len: Option<u32>,
+    pub uids: Vec<u64>,
+    pub room_ids: Vec<i64>,
+    // 价值（元）区间 [minValue, maxValue)：付费礼物 / 大航海 / 醒目留言，其它事件为 0
+    pub min_value: Option<f64>,
+    pub max_value: Option<f64>,
+}
+
+/// 视觉字段覆盖，字段与推送 JSON 一致；未填写的保持原样式
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StyleOverride {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font_size: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stroke_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stroke_width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub typing_speed: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_duration: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fade_duration: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shake_amplitude: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub random_tilt: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum RuleAction {
+    Drop,
+    // 放行：跳过后续规则与其它过滤条件（关键词、长度、粉丝牌、去重等；禁言与用户黑名单仍生效）
+    Allow,
+    Restyle { style: StyleOverride },
+    // pattern 为空时整条替换为 replacement（支持 {text} {user} 占位）；否则按正则替换（支持 $1）
+    Rewrite {
+        #[serde(default)]
+        pattern: Option<String>,
+        replacement: String,
+    },
+    // 推送 JSON 里附加 tags，供 overlay 按标签定制展示
+    Tag { tag: String },
+}
+
+struct CompiledRule {
+    rule: FilterRule,
+    // 日志与 dry-run 中显示的规则名；未命名时为 “rule #N”（N 为配置列表中的序号，从 1 开始，含已停用的规则）
+    label: String,
+    text_regex: Option<Regex>,
+    rewrite_regex: Option<Regex>,
+}
+
+/// 编译后的规则列表；只在 set_danmu_filter_config 时构建
+#[derive(Default)]
+pub struct RuleSet {
+    rules: Vec<CompiledRule>,
+}
+
+/// 规则对一条事件的处理结果
+#[derive(Debug, Clone, Default)]
+pub struct RuleEffects {
+    // 命中 drop 的规则名（未命名时为 “rule #N”）
+    pub dropped_by: Option<String>,
+    pub allowed: bool,
+    pub style: StyleOverride,
+    pub tags: Vec<String>,
+}
+
+fn compile_regex(pattern: &str) -> Result<Regex, AppError> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|e| AppError::InvalidArgument(format!("规则正则无效 {pattern:?}: {e}")))
+}
+
+/// 事件价值（元）
+fn event_value(event: &LiveEvent) -> f64 {
+    match event {
+        LiveEvent::Gift(g) if g.coin_type == "gold" => g.total_coin as f64 / 1000.0,
+        LiveEvent::GuardBuy(g) => (g.price * g.num) as f64 / 1000.0,
+        LiveEvent::SuperChat(sc) => sc.price as f64,
+        _ => 0.0,
+    }
+}
+
+impl StyleOverride {
+    fn merge(&mut self, other: &StyleOverride) {
+        macro_rules! take {
+            ($($field:ident),*) => {
+                $(if other.$field.is_some() {
+                    self.$field = other.$field.clone();
+                })*
+            };
+        }
+        take!(font_size, color, stroke_color, stroke_width, typing_speed, display_duration, fade_duration, shake_amplitude, random_tilt);
+    }
+}
+
+impl RuleEffects {
+    /// 把样式覆盖与标签写进 apply_style_to_sse_message 生成的 JSON
+    pub fn apply_to(&self, msg: &mut serde_json::Value) {
+        let Some(map) = msg.as_object_mut() else {
+            return;
+        };
+        if let Ok(serde_json::Value::Object(style)) = serde_json::to_value(&self.style) {
+            map.extend(style);
+        }
+        if !self.tags.is_empty() {
+            map.insert("tags".to_string(), serde_json::json!(self.tags));
+        }
+    }
+}
+
+impl CompiledRule {
+    fn matches(&self, event: &LiveEvent) -> bool {
+        let c = &self.rule.conditions;
+        let common = event.common();
+
+        if !c.types.is_empty() && !c.types.iter().any(|t| t == event.kind()) {
+            return false;
+        }
+        if !c.guard_levels.is_empty() && !c.guard_levels.contains(&u8::from(common.guard_level)) {
+            return false;
+        }
+        if c.has_own_medal.is_some_and(|v| v != common.medal.has_own_medal) {
+            return false;
+        }
+        if c.min_medal_level.is_some_and(|min| common.medal.medal_level.unwrap_or(0) < min) {
+            return false;
+        }
+        if let Some(name) = c.medal_name.as_deref().filter(|s| !s.is_empty()) {
+            if common.medal.medal_name.as_deref() != Some(name) {
+                return false;
+            }
+        }
+        if c.is_streamer.is_some_and(|v| v != common.user.is_streamer) {
+            return false;
+        }
+        if c.is_moderator.is_some_and(|v| v != common.user.is_moderator) {
+            return false;
+        }
+        let len = common.text.chars().count() as u32;
+        if c.min_len.is_some_and(|min| len < min) || c.max_len.is_some_and(|max| len > max) {
+            return false;
+        }
+        if !c.uids.is_empty() && !common.user.uid.is_some_and(|uid| c.uids.contains(&uid)) {
+            return false;
+        }
+        if !c.room_ids.is_empty() && !common.source.room_id.is_some_and(|id| c.room_ids.contains(&id)) {
+            return false;
+        }
+        if c.min_value.is_some() || c.max_value.is_some() {
+            let value = event_value(event);
+            if c.min_value.is_some_and(|min| value < min) || c.max_value.is_some_and(|max| value >= max) {
+                return false;
+            }
+        }
+        if let Some(re) = &self.text_regex {
+            if !re.is_match(&common.text) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl RuleSet {
+    pub fn compile(rules: &[FilterRule]) -> Result<Self, AppError> {
+        let rules = rules
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.enabled)
+            .map(|(index, rule)| {
+                let text_regex = rule
+                    .conditions
+                    .text_regex
+                    .as_deref()
+                    .filter(|p| !p.is_empty())
+                    .map(compile_regex)
+                    .transpose()?;
+                let rewrite_regex = match &rule.action {
+                    RuleAction::Rewrite { pattern: Some(p), .. } if !p.is_empty() => Some(compile_regex(p)?),
+                    _ => None,
+                };
+                let label = match rule.name.trim() {
+                    "" => format!("rule #{}", index + 1),
+                    name => name.to_string(),
+                };
+                Ok(CompiledRule {
+                    rule: rule.clone(),
+                    label,
+                    text_regex,
+                    rewrite_regex,
+                })
+            })
+            .collect::<Result<Vec<_>, AppError>>()?;
+        Ok(Self { rules })
+    }
+
+    /// 依次应用规则；rewrite 直接修改事件文本
+    pub fn apply(&self, event: &mut LiveEvent) -> RuleEffects {
+        let mut effects = RuleEffects::default();
+        // 删除通知只用于撤回，不参与规则
+        if matches!(event, LiveEvent::SuperChatDelete(_)) {
+            return effects;
+        }
+
+        for compiled in &self.rules {
+            if !compiled.matches(event) {
+                continue;
+            }
+            match &compiled.rule.action {
+                RuleAction::Drop => {
+                    effects.dropped_by = Some(compiled.label.clone());
+                    break;
+                }
+                RuleAction::Allow => {
+                    effects.allowed = true;
+                    break;
+                }
+                RuleAction::Restyle { style } => effects.style.merge(style),
+                RuleAction::Rewrite { replacement, .. } => {
+                    let common = event.common_mut();
+                    common.text = match &compiled.rewrite_regex {
+                        Some(re) => re.replace_all(&common.text, replacement.as_str()).into_owned(),
+                        None => PLACEHOLDER
+                            .replace_all(replacement, |caps: &Captures| match &caps[1] {
+                                "text" => common.text.clone(),
+                                _ => common.user.name.clone(),
+                            })
+                            .into_owned(),
+                    };
+                }
+                RuleAction::Tag { tag } => {
+                    if !effects.tags.contains(tag) {
+                        effects.tags.push(tag.clone());
+                    }
+                }
+            }
+        }
+        effects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::live_event::{DanmuEvent, EventCommon};
+
+    #[test]
+    fn rewrite_placeholders_are_expanded_once() {
+        let rules: Vec<FilterRule> = serde_json::from_str(
+            r#"[{"action":{"type":"rewrite","replacement":"{user} 说：{text}"}}]"#,
+        )
+        .unwrap();
+        let set = RuleSet::compile(&rules).unwrap();
+        let common = EventCommon::new("我是 {user} {text}".to_string(), Some("甲".to_string()), None);
+        let mut event = LiveEvent::Danmu(DanmuEvent { common });
+        set.apply(&mut event);
+        assert_eq!(event.common().text, "甲 说：我是 {user} {text}");
+    }
+
+    #[test]
+    fn unnamed_drop_rules_are_reported_by_position() {
+        let rules: Vec<FilterRule> = serde_json::from_str(
+            r#"[
+                {"name": "停用", "enabled": false, "action": {"type": "drop"}},
+                {"conditions": {"textRegex": "广告"}, "action": {"type": "drop"}},
+                {"name": "  ", "conditions": {"textRegex": "代刷"}, "action": {"type": "drop"}},
+                {"name": "刷屏", "conditions": {"textRegex": "666"}, "action": {"type": "drop"}}
+            ]"#,
+        )
+        .unwrap();
+        let set = RuleSet::compile(&rules).unwrap();
+        let dropped_by = |text: &str| {
+            let common = EventCommon::new(text.to_string(), Some("甲".to_string()), None);
+            set.apply(&mut LiveEvent::Danmu(DanmuEvent { common })).dropped_by
+        };
+        assert_eq!(dropped_by("广告位招租").as_deref(), Some("rule #2"));
+        assert_eq!(dropped_by("代刷粉丝").as_deref(), Some("rule #3"));
+        assert_eq!(dropped_by("666").as_deref(), Some("刷屏"));
+        assert_eq!(dropped_by("晚上好"), None);
+    }
+}
@@ -0,0 +1,192 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::bili_websocket_client::{self, DanmuFilterConfig, FilterEvaluation};
+use crate::error::AppError;
+use crate::live_event::LiveEvent;
+
+// 单次测试最多评估的事件数
+const MAX_TEST_EVENTS: usize = 10_000;
+
+/// 过滤 dry-run 请求：候选配置 + 单条样例事件 / 一批事件 / 录制的事件日志。
+/// 样例弹幕最少只需 {"type":"danmu","text":"..."}，可再加 user / uid / timestamp（Unix 毫秒，影响去重窗口）；
+/// 其它字段缺省补齐（用户名“匿名用户”、时间为当前时刻）
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FilterTestRequest {
+    // 为空时使用当前生效的配置
+    pub config: Option<DanmuFilterConfig>,
+    pub event: Option<LiveEvent>,
+    pub events: Vec<LiveEvent>,
+    // 事件日志（JSON Lines，如导出的 transcript）；非事件行跳过
+    pub path: Option<PathBuf>,
+    // 按该连接的房间信息判断粉丝牌/主播；为空时只用事件自带字段
+    pub connection_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterTestReport {
+    pub total: usize,
+    pub dropped: usize,
+    pub forwarded: usize,
+    // 丢弃原因 -> 条数
+    pub by_reason: BTreeMap<String, usize>,
+    pub results: Vec<FilterEvaluation>,
+}
+
+fn load_events(path: &PathBuf, limit: usize) -> Result<Vec<LiveEvent>, AppError> {
+    let file = File::open(path).map_err(|e| AppError::Io(format!("打开事件日志失败 {}: {e}", path.display())))?;
+    let mut events = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| AppError::Io(format!("读取事件日志失败: {e}")))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // 样式配置、retract 等非事件行直接跳过
+        if let Ok(event) = serde_json::from_str::<LiveEvent>(&line) {
+            events.push(event);
+            if events.len() >= limit {
+                break;
+            }
+        }
+    }
+    Ok(events)
+}
+
+/// 用候选配置评估事件，返回每条是否会被丢弃及原因；不修改生效配置、去重窗口与统计
+pub async fn run(req: FilterTestRequest) -> Result<FilterTestReport, AppError> {
+    let mut events = req.events;
+    events.extend(req.event);
+    if let Some(path) = &req.path {
+        let remaining = MAX_TEST_EVENTS.saturating_sub(events.len());
+        events.extend(load_events(path, remaining)?);
+    }
+    if events.is_empty() {
+        return Err(AppError::InvalidArgument("缺少测试事件（event / events / path）".to_string()));
+    }
+    if events.len() > MAX_TEST_EVENTS {
+        return Err(AppError::InvalidArgument(format!("测试事件过多，最多 {} 条", MAX_TEST_EVENTS)));
+    }
+
+    let cfg = match req.config {
+        Some(cfg) => cfg,
+        None => bili_websocket_client::danmu_filter_config().await,
+    };
+    let results = bili_websocket_client::dry_run_filter(cfg, events, req.connection_id.as_deref()).await?;
+
+    let mut by_reason = BTreeMap::new();
+    for reason in results.iter().filter_map(|r| r.reason) {
+        let key = serde_json::to_value(reason)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default();
+        *by_reason.entry(key).or_insert(0) += 1;
+    }
+    let dropped = results.iter().filter(|r| r.dropped).count();
+    Ok(FilterTestReport {
+        total: results.len(),
+        dropped,
+        forwarded: results.len() - dropped,
+        by_reason,
+        results,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bili_websocket_client::DropReason;
+
+    #[tokio::test]
+    async fn accepts_minimal_sample_event() {
+        let req: FilterTestRequest = serde_json::from_str(
+            r#"{
+                "config": {"blacklistEnabled": true, "keywordBlacklist": ["广告"], "onlyFansMedal": false, "onlyStreamer": false, "hideStreamer": false},
+                "event": {"type": "danmu", "text": "广告加微信", "user": "甲"}
+            }"#,
+        )
+        .unwrap();
+        let report = run(req).await.unwrap();
+        assert_eq!(report.total, 1);
+        assert_eq!(report.dropped, 1);
+        assert_eq!(report.by_reason.get("filtered"), Some(&1));
+        assert_eq!(report.results[0].user, "甲");
+    }
+
+    #[tokio::test]
+    async fn filtered_danmu_does_not_enter_dedup_window() {
+        let req: FilterTestRequest = serde_json::from_str(
+            r#"{
+                "config": {"blacklistEnabled": false, "keywordBlacklist": [], "onlyFansMedal": true, "onlyStreamer": false, "hideStreamer": false, "dedup": {"enabled": true}},
+                "events": [
+                    {"type": "danmu", "text": "冲冲冲", "user": "路人", "uid": 1, "timestamp": 1000},
+                    {"type": "danmu", "text": "冲冲冲", "user": "粉丝", "uid": 2, "timestamp": 2000, "hasOwnMedal": true},
+                    {"type": "danmu", "text": "冲冲冲", "user": "粉丝二号", "uid": 3, "timestamp": 3000, "hasOwnMedal": true}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let report = run(req).await.unwrap();
+        let reasons = report.results.iter().map(|r| r.reason).collect::<Vec<_>>();
+        assert_eq!(reasons, [Some(DropReason::Filtered), None, Some(DropReason::Duplicate)]);
+    }
+
+    #[tokio::test]
+    async fn dedups_event_log_by_event_time() {
+        let path = std::env::temp_dir().join(format!("filter-test-{}.jsonl", crate::live_event::new_event_id()));
+        std::fs::write(&path, include_str!("../tests/fixtures/replay_events.jsonl")).unwrap();
+        let req = FilterTestRequest {
+            config: Some(DanmuFilterConfig {
+                blacklist_enabled: true,
+                keyword_blacklist: vec!["广告".to_string()],
+                dedup: crate::dedup::DedupConfig {
+                    enabled: true,
+                    ..Default::default()
+                },
+                ..DanmuFilterConfig::default()
+            }),
+            path: Some(path.clone()),
+            ..FilterTestRequest::default()
+        };
+        let report = run(req).await;
+        let _ = std::fs::remove_file(&path);
+        let report = report.unwrap();
+
+        // 前两条“大家好”相隔 2 秒判为重复；第三条按事件时间已出 10 秒窗口
+        assert_eq!(report.total, 5);
+        assert_eq!(report.forwarded, 3);
+        assert_eq!(report.by_reason.get("duplicate"), Some(&1));
+        assert_eq!(report.by_reason.get("filtered"), Some(&1));
+    }
+
+    #[tokio::test]
+    async fn keyword_normalization_paths_apply_to_danmu() {
+        let req: FilterTestRequest = serde_json::from_str(
+            r#"{
+                "config": {
+                    "blacklistEnabled": true, "keywordBlacklist": ["傻逼", "加微信"], "regexBlacklist": ["v[0-9]{3,}"],
+                    "normalizeText": true, "pinyinMatch": true,
+                    "onlyFansMedal": false, "onlyStreamer": false, "hideStreamer": false
+                },
+                "events": [
+                    {"type": "danmu", "text": "加 微.信"},
+                    {"type": "danmu", "text": "ＶＸ：Ｖ１２３４"},
+                    {"type": "danmu", "text": "主播沙比"},
+                    {"type": "danmu", "text": "SB"},
+                    {"type": "danmu", "text": "是不是真的"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let report = run(req).await.unwrap();
+        let dropped = report.results.iter().map(|r| r.dropped).collect::<Vec<_>>();
+        assert_eq!(dropped, [true, true, true, true, false]);
+    }
+}
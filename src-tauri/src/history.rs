@@ -0,0 +1,435 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{mpsc, Arc},
+};
+
+use once_cell::sync::Lazy;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use crate::error::AppError;
+use crate::live_event::LiveEvent;
+
+/// 历史记录库：转发出去的弹幕/礼物/醒目留言落到本地 SQLite，供房管回查某个用户之前说过什么。
+/// 未打开（启动时打开失败等）时记录与查询都直接跳过/报错，不影响正常转发。
+/// 查询用这里的连接；写入交给独立的写线程（见 run_writer），转发路径上只是往通道里塞一行。
+static HISTORY_DB: Lazy<Arc<Mutex<Option<Connection>>>> = Lazy::new(|| Arc::new(Mutex::new(None)));
+// 写线程的通道；重新打开库时替换，旧线程随发送端被丢弃而退出
+static HISTORY_WRITER: Lazy<std::sync::Mutex<Option<mpsc::Sender<HistoryRow>>>> = Lazy::new(|| std::sync::Mutex::new(None));
+
+// 写线程单个事务最多写入的条数（刷屏时把积压的行合并成一个事务）
+const WRITE_BATCH: usize = 500;
+
+// 单次查询最多返回的条数
+const HISTORY_DEFAULT_LIMIT: u32 = 100;
+const HISTORY_MAX_LIMIT: u32 = 1000;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS events (
+    id            INTEGER PRIMARY KEY AUTOINCREMENT,
+    event_id      TEXT NOT NULL,
+    type          TEXT NOT NULL,
+    connection_id TEXT,
+    room_id       INTEGER,
+    uid           INTEGER,
+    user          TEXT NOT NULL,
+    text          TEXT NOT NULL,
+    guard_level   INTEGER NOT NULL DEFAULT 0,
+    medal_name    TEXT,
+    medal_level   INTEGER,
+    has_own_medal INTEGER NOT NULL DEFAULT 0,
+    gift_name     TEXT,
+    gift_num      INTEGER,
+    coin_type     TEXT,
+    total_coin    INTEGER,
+    value         INTEGER,
+    timestamp     INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_events_timestamp ON events(timestamp);
+CREATE INDEX IF NOT EXISTS idx_events_uid ON events(uid);
+CREATE INDEX IF NOT EXISTS idx_events_user ON events(user);
+";
+
+fn open_connection(path: &Path) -> Result<Connection, AppError> {
+    let conn = Connection::open(path).map_err(|e| AppError::Database(format!("打开历史记录库失败: {e}")))?;
+    // WAL：写入（转发）与查询互不阻塞；WAL 下 NORMAL 只在检查点 fsync，断电最多丢最近的几条
+    let _ = conn.pragma_update(None, "journal_mode", "WAL");
+    let _ = conn.pragma_update(None, "synchronous", "NORMAL");
+    Ok(conn)
+}
+
+pub async fn open_history_db(path: &Path) -> Result<(), AppError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| AppError::Io(format!("创建配置目录失败: {e}")))?;
+    }
+    let writer = open_connection(path)?;
+    writer
+        .execute_batch(SCHEMA)
+        .map_err(|e| AppError::Database(format!("初始化历史记录库失败: {e}")))?;
+    let reader = open_connection(path)?;
+
+    let (tx, rx) = mpsc::channel();
+    std::thread::Builder::new()
+        .name("history-writer".to_string())
+        .spawn(move || run_writer(writer, rx))
+        .map_err(|e| AppError::Io(format!("启动历史记录写线程失败: {e}")))?;
+
+    *HISTORY_DB.lock().await = Some(reader);
+    if let Ok(mut sender) = HISTORY_WRITER.lock() {
+        *sender = Some(tx);
+    }
+    Ok(())
+}
+
+// 待写入的一行
+struct HistoryRow {
+    event_id: String,
+    kind: &'static str,
+    connection_id: Option<String>,
+    room_id: Option<i64>,
+    uid: Option<i64>,
+    user: String,
+    text: String,
+    guard_level: u8,
+    medal_name: Option<String>,
+    medal_level: Option<u32>,
+    has_own_medal: bool,
+    gift_name: Option<String>,
+    gift_num: Option<i64>,
+    coin_type: Option<String>,
+    total_coin: Option<i64>,
+    value: Option<i64>,
+    timestamp: i64,
+}
+
+/// 写线程：阻塞等待第一行，再把已积压的行一并放进一个事务
+fn run_writer(mut conn: Connection, rx: mpsc::Receiver<HistoryRow>) {
+    while let Ok(first) = rx.recv() {
+        let mut batch = vec![first];
+        while batch.len() < WRITE_BATCH {
+            match rx.try_recv() {
+                Ok(row) => batch.push(row),
+                Err(_) => break,
+            }
+        }
+        if let Err(e) = insert_batch(&mut conn, &batch) {
+            eprintln!("[History] 写入失败（{} 条）: {e}", batch.len());
+        }
+    }
+}
+
+fn insert_batch(conn: &mut Connection, rows: &[HistoryRow]) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    {
+        let mut stmt = tx.prepare_cached(
+            "INSERT INTO events (event_id, type, connection_id, room_id, uid, user, text, guard_level, medal_name, medal_level, has_own_medal, gift_name, gift_num, coin_type, total_coin, value, timestamp)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
+        )?;
+        for row in rows {
+            stmt.execute(params![
+                row.event_id,
+                row.kind,
+                row.connection_id,
+                row.room_id,
+                row.uid,
+                row.user,
+                row.text,
+                row.guard_level,
+                row.medal_name,
+                row.medal_level,
+                row.has_own_medal,
+                row.gift_name,
+                row.gift_num,
+                row.coin_type,
+                row.total_coin,
+                row.value,
+                row.timestamp,
+            ])?;
+        }
+    }
+    tx.commit()
+}
+
+/// 记录一条已转发的事件（只记录弹幕/礼物/醒目留言）；只入队，不等待写盘
+pub fn record_event(event: &LiveEvent) {
+    let Some(row) = history_row(event) else {
+        return;
+    };
+    let Ok(writer) = HISTORY_WRITER.lock() else {
+        return;
+    };
+    if let Some(writer) = writer.as_ref() {
+        let _ = writer.send(row);
+    }
+}
+
+fn history_row(event: &LiveEvent) -> Option<HistoryRow> {
+    // value 统一为金瓜子（1000 = 1 元，与排行榜一致）：银瓜子礼物免费记 0，其它币种（Twitch bits）不折算
+    let (gift_name, gift_num, coin_type, total_coin, value) = match event {
+        LiveEvent::Danmu(_) => (None, None, None, None, None),
+        LiveEvent::Gift(g) => {
+            let value = match g.coin_type.as_str() {
+                "gold" => Some(g.total_coin as i64),
+                "silver" => Some(0),
+                _ => None,
+            };
+            (
+                Some(g.gift_name.clone()),
+                Some(g.gift_num as i64),
+                Some(g.coin_type.clone()),
+                Some(g.total_coin as i64),
+                value,
+            )
+        }
+        LiveEvent::SuperChat(sc) => (None, None, None, None, Some(sc.price as i64 * 1000)),
+        _ => return None,
+    };
+
+    let common = event.common();
+    Some(HistoryRow {
+        event_id: common.id.clone(),
+        kind: event.kind(),
+        connection_id: common.source.connection_id.clone(),
+        room_id: common.source.room_id,
+        uid: common.user.uid.map(|v| v as i64),
+        user: common.user.name.clone(),
+        text: common.text.clone(),
+        guard_level: u8::from(common.guard_level),
+        medal_name: common.medal.medal_name.clone(),
+        medal_level: common.medal.medal_level,
+        has_own_medal: common.medal.has_own_medal,
+        gift_name,
+        gift_num,
+        coin_type,
+        total_coin,
+        value,
+        timestamp: common.timestamp as i64,
+    })
+}
+
+/// 查询条件（/api/history 与 Tauri 命令共用）
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryQuery {
+    // 弹幕内容关键词（子串匹配）
+    pub q: Option<String>,
+    // 用户名（子串匹配）或 uid（纯数字时同时按 uid 精确匹配）
+    pub user: Option<String>,
+    // 只查该时刻之后（Unix 毫秒）
+    pub since: Option<u64>,
+    // 事件类型：danmu / gift / superChat
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub room_id: Option<i64>,
+    pub limit: Option<u32>,
+}
+
+impl HistoryQuery {
+    /// 从 HTTP query 参数解析（token 等其它参数忽略）
+    pub fn from_params(params: &HashMap<String, String>) -> Result<Self, AppError> {
+        let get = |key: &str| params.get(key).map(|s| s.trim()).filter(|s| !s.is_empty());
+        let parse_num = |key: &str| -> Result<Option<u64>, AppError> {
+            get(key)
+                .map(|s| {
+                    s.parse::<u64>()
+                        .map_err(|_| AppError::InvalidArgument(format!("{key} 必须是数字")))
+                })
+                .transpose()
+        };
+        Ok(Self {
+            q: get("q").map(|s| s.to_string()),
+            user: get("user").map(|s| s.to_string()),
+            since: parse_num("since")?,
+            kind: get("type").map(|s| s.to_string()),
+            room_id: parse_num("roomId")?.map(|v| v as i64),
+            limit: parse_num("limit")?.map(|v| v.min(u64::from(HISTORY_MAX_LIMIT)) as u32),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub connection_id: Option<String>,
+    pub room_id: Option<i64>,
+    pub uid: Option<i64>,
+    pub user: String,
+    pub text: String,
+    pub guard_level: u8,
+    pub medal_name: Option<String>,
+    pub medal_level: Option<u32>,
+    pub has_own_medal: bool,
+    pub gift_name: Option<String>,
+    pub gift_num: Option<i64>,
+    // 礼物的瓜子类型（gold / silver / bits）与总数（单价 x 数量，单位同 coinType）
+    pub coin_type: Option<String>,
+    pub total_coin: Option<i64>,
+    // 价值：金瓜子（1000 = 1 元）；弹幕与无法折算的币种为 null
+    pub value: Option<i64>,
+    pub timestamp: i64,
+}
+
+/// LIKE 子串匹配，转义用户输入里的通配符
+fn like_pattern(s: &str) -> String {
+    let escaped = s.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{escaped}%")
+}
+
+/// 按时间倒序返回最近的记录
+pub async fn query_history(query: &HistoryQuery) -> Result<Vec<HistoryEntry>, AppError> {
+    let db = HISTORY_DB.lock().await;
+    let conn = db
+        .as_ref()
+        .ok_or_else(|| AppError::Database("历史记录库未打开".to_string()))?;
+    query_events(conn, query)
+}
+
+fn query_events(conn: &Connection, query: &HistoryQuery) -> Result<Vec<HistoryEntry>, AppError> {
+    let mut sql = String::from(
+        "SELECT event_id, type, connection_id, room_id, uid, user, text, guard_level, medal_name, medal_level, has_own_medal, gift_name, gift_num, coin_type, total_coin, value, timestamp FROM events WHERE 1=1",
+    );
+    let mut args: Vec<rusqlite::types::Value> = Vec::new();
+
+    if let Some(q) = query.q.as_deref().filter(|s| !s.is_empty()) {
+        sql.push_str(" AND text LIKE ? ESCAPE '\\'");
+        args.push(like_pattern(q).into());
+    }
+    if let Some(user) = query.user.as_deref().filter(|s| !s.is_empty()) {
+        match user.parse::<i64>() {
+            Ok(uid) => {
+                sql.push_str(" AND (uid = ? OR user LIKE ? ESCAPE '\\')");
+                args.push(uid.into());
+            }
+            Err(_) => sql.push_str(" AND user LIKE ? ESCAPE '\\'"),
+        }
+        args.push(like_pattern(user).into());
+    }
+    if let Some(since) = query.since {
+        sql.push_str(" AND timestamp >= ?");
+        args.push((since as i64).into());
+    }
+    if let Some(kind) = query.kind.as_deref().filter(|s| !s.is_empty()) {
+        sql.push_str(" AND type = ?");
+        args.push(kind.to_string().into());
+    }
+    if let Some(room_id) = query.room_id {
+        sql.push_str(" AND room_id = ?");
+        args.push(room_id.into());
+    }
+    let limit = query.limit.unwrap_or(HISTORY_DEFAULT_LIMIT).min(HISTORY_MAX_LIMIT);
+    sql.push_str(" ORDER BY timestamp DESC, id DESC LIMIT ?");
+    args.push(i64::from(limit).into());
+
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|e| AppError::Database(format!("查询历史记录失败: {e}")))?;
+    let rows = stmt
+        .query_map(rusqlite::params_from_iter(args), |row| {
+            Ok(HistoryEntry {
+                id: row.get(0)?,
+                kind: row.get(1)?,
+                connection_id: row.get(2)?,
+                room_id: row.get(3)?,
+                uid: row.get(4)?,
+                user: row.get(5)?,
+                text: row.get(6)?,
+                guard_level: row.get(7)?,
+                medal_name: row.get(8)?,
+                medal_level: row.get(9)?,
+                has_own_medal: row.get(10)?,
+                gift_name: row.get(11)?,
+                gift_num: row.get(12)?,
+                coin_type: row.get(13)?,
+                total_coin: row.get(14)?,
+                value: row.get(15)?,
+                timestamp: row.get(16)?,
+            })
+        })
+        .map_err(|e| AppError::Database(format!("查询历史记录失败: {e}")))?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| AppError::Database(format!("读取历史记录失败: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        let events = [
+            r##"{"type":"danmu","text":"主播晚上好","user":"甲","uid":1,"timestamp":1000,"time":1000,"color":"#ffffff","size":32,"timestampText":""}"##,
+            r##"{"type":"danmu","text":"100%好看","user":"乙_二号","uid":2,"timestamp":2000,"time":2000,"color":"#ffffff","size":32,"timestampText":""}"##,
+            r##"{"type":"gift","text":"投喂 辣条 x10","user":"甲","uid":1,"timestamp":3000,"time":3000,"color":"#ffffff","size":32,"timestampText":"","giftName":"辣条","giftNum":10,"coinType":"silver","price":100,"totalCoin":1000}"##,
+            r##"{"type":"superChat","text":"SC：晚上好","user":"丙","uid":3,"timestamp":4000,"time":4000,"color":"#ffffff","size":32,"timestampText":"","message":"晚上好","price":30,"duration":60}"##,
+            r##"{"type":"enter","text":"进入直播间","user":"丁","uid":4,"timestamp":5000,"time":5000,"color":"#ffffff","size":32,"timestampText":""}"##,
+        ];
+        let rows = events
+            .iter()
+            .filter_map(|json| history_row(&serde_json::from_str(json).unwrap()))
+            .collect::<Vec<_>>();
+        insert_batch(&mut conn, &rows).unwrap();
+        conn
+    }
+
+    fn users(conn: &Connection, query: HistoryQuery) -> Vec<String> {
+        query_events(conn, &query).unwrap().into_iter().map(|e| e.user).collect()
+    }
+
+    #[test]
+    fn records_only_danmu_gifts_and_super_chats() {
+        let conn = test_db();
+        let all = query_events(&conn, &HistoryQuery::default()).unwrap();
+        // 按时间倒序，互动事件不入库
+        assert_eq!(all.iter().map(|e| e.timestamp).collect::<Vec<_>>(), [4000, 3000, 2000, 1000]);
+        // 价值统一为金瓜子：银瓜子礼物记 0，醒目留言 30 元 = 30000
+        assert_eq!(all[0].value, Some(30_000));
+        assert_eq!((all[1].coin_type.as_deref(), all[1].total_coin, all[1].value), (Some("silver"), Some(1000), Some(0)));
+        assert_eq!(all[3].value, None);
+    }
+
+    #[test]
+    fn filters_by_text_user_time_and_type() {
+        let conn = test_db();
+        let q = |s: &str| HistoryQuery {
+            q: Some(s.to_string()),
+            ..HistoryQuery::default()
+        };
+        assert_eq!(users(&conn, q("晚上好")), ["丙", "甲"]);
+        // 通配符按字面匹配
+        assert_eq!(users(&conn, q("100%")), ["乙_二号"]);
+        assert_eq!(users(&conn, q("%")).len(), 1);
+
+        let by_user = |s: &str| HistoryQuery {
+            user: Some(s.to_string()),
+            ..HistoryQuery::default()
+        };
+        assert_eq!(users(&conn, by_user("甲")), ["甲", "甲"]);
+        assert_eq!(users(&conn, by_user("3")), ["丙"]);
+        assert_eq!(users(&conn, by_user("_")), ["乙_二号"]);
+
+        let since = HistoryQuery {
+            since: Some(2000),
+            ..HistoryQuery::default()
+        };
+        assert_eq!(users(&conn, since), ["丙", "甲", "乙_二号"]);
+
+        let gifts = HistoryQuery {
+            kind: Some("gift".to_string()),
+            user: Some("1".to_string()),
+            ..HistoryQuery::default()
+        };
+        assert_eq!(users(&conn, gifts), ["甲"]);
+
+        let limited = HistoryQuery {
+            limit: Some(1),
+            ..HistoryQuery::default()
+        };
+        assert_eq!(users(&conn, limited), ["丙"]);
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+use regex::{Regex, RegexBuilder};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::error::AppError;
+
+// 单条正则编译后的体积上限，防止误填超大正则拖慢每条弹幕
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+// 繁体/异体 -> 简体 单字对照（数据文件每两个字符一组）
+static T2S: Lazy<HashMap<char, char>> = Lazy::new(|| {
+    let chars = include_str!("../data/t2s.txt")
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .flat_map(|line| line.chars())
+        .collect::<Vec<_>>();
+    chars.chunks_exact(2).map(|pair| (pair[0], pair[1])).collect()
+});
+
+// U+4E00..U+9FFF 汉字 -> 无声调拼音（数据文件每行：音节 + 读该音的汉字）
+static PINYIN: Lazy<HashMap<char, &'static str>> = Lazy::new(|| {
+    include_str!("../data/pinyin.txt")
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once(' '))
+        .flat_map(|(syllable, chars)| chars.chars().map(move |c| (c, syllable)))
+        .collect()
+});
+
+fn pinyin_of(c: char) -> Option<&'static str> {
+    PINYIN.get(&c).copied()
+}
+
+fn pinyin_initial(c: char) -> Option<char> {
+    pinyin_of(c)?.chars().next()
+}
+
+/// 归一化：NFKC（全角 -> 半角、兼容字符展开）、小写、繁体 -> 简体，并去掉空白/标点/符号/零宽字符。
+/// 用来识别“傻 逼”“ＳＢ”“傻.逼”“說”这类绕过子串匹配的写法。
+pub fn normalize(text: &str) -> String {
+    text.nfkc()
+        .flat_map(char::to_lowercase)
+        .map(|c| T2S.get(&c).copied().unwrap_or(c))
+        .filter(|c| c.is_alphanumeric())
+        .collect()
+}
+
+struct Keyword {
+    raw: String,
+    normalized: Vec<char>,
+    // 含汉字才做拼音匹配
+    has_cjk: bool,
+}
+
+/// 由 DanmuFilterConfig 编译出的关键词/正则过滤器；只在 set_danmu_filter_config 时构建一次
+#[derive(Default)]
+pub struct KeywordFilter {
+    keywords: Vec<Keyword>,
+    regexes: Vec<Regex>,
+    normalize: bool,
+    pinyin: bool,
+}
+
+impl KeywordFilter {
+    pub fn compile(
+        keywords: &[String],
+        patterns: &[String],
+        normalize_text: bool,
+        pinyin: bool,
+    ) -> Result<Self, AppError> {
+        let keywords = keywords
+            .iter()
+            .map(|kw| kw.trim())
+            .filter(|kw| !kw.is_empty())
+            .map(|kw| {
+                let normalized = normalize(kw).chars().collect::<Vec<_>>();
+                Keyword {
+                    raw: kw.to_string(),
+                    has_cjk: normalized.iter().any(|c| pinyin_initial(*c).is_some()),
+                    normalized,
+                }
+            })
+            .collect();
+
+        let regexes = patterns
+            .iter()
+            .map(|p| p.trim())
+            .filter(|p| !p.is_empty())
+            .map(|p| {
+                RegexBuilder::new(p)
+                    .case_insensitive(true)
+                    .size_limit(REGEX_SIZE_LIMIT)
+                    .build()
+                    .map_err(|e| AppError::InvalidArgument(format!("正则表达式无效 {p:?}: {e}")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            keywords,
+            regexes,
+            normalize: normalize_text,
+            pinyin,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keywords.is_empty() && self.regexes.is_empty()
+    }
+
+    /// 命中时返回命中的规则（用于调试日志）
+    pub fn find_match(&self, text: &str) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+
+        for kw in &self.keywords {
+            if text.contains(kw.raw.as_str()) {
+                return Some(format!("keyword={:?}", kw.raw));
+            }
+        }
+
+        let normalized = (self.normalize || self.pinyin).then(|| normalize(text));
+
+        for re in &self.regexes {
+            if re.is_match(text) || (self.normalize && normalized.as_deref().is_some_and(|n| re.is_match(n))) {
+                return Some(format!("regex={:?}", re.as_str()));
+            }
+        }
+
+        let chars = normalized?.chars().collect::<Vec<_>>();
+        for kw in &self.keywords {
+            if kw.normalized.is_empty() {
+                continue;
+            }
+            if self.normalize && contains_seq(&chars, &kw.normalized, |a, b| a == b) {
+                return Some(format!("keyword={:?} (normalized)", kw.raw));
+            }
+            // 单字关键词按首字母/同音匹配会误伤大量正常弹幕，至少两个字才做拼音匹配
+            if self.pinyin && kw.has_cjk && kw.normalized.len() >= 2 && contains_pinyin_abbr(&chars, &kw.normalized) {
+                return Some(format!("keyword={:?} (pinyin)", kw.raw));
+            }
+        }
+        None
+    }
+}
+
+/// 拼音匹配：关键词里的汉字可以由弹幕里同首字母的英文字母代替（“傻逼” ~ “sb” / “傻b”），
+/// 或由同音字代替（“沙比”，不计声调）。两个汉字之间只比较整个音节，不按首字母相等处理（否则“是不是”也会命中“傻逼”）。
+fn pinyin_eq(text_char: char, kw_char: char) -> bool {
+    if text_char == kw_char {
+        return true;
+    }
+    if text_char.is_ascii_lowercase() {
+        return pinyin_initial(kw_char) == Some(text_char);
+    }
+    matches!((pinyin_of(text_char), pinyin_of(kw_char)), (Some(a), Some(b)) if a == b)
+}
+
+fn contains_seq(haystack: &[char], needle: &[char], eq: impl Fn(char, char) -> bool) -> bool {
+    needle.len() <= haystack.len()
+        && haystack
+            .windows(needle.len())
+            .any(|w| w.iter().zip(needle).all(|(a, b)| eq(*a, *b)))
+}
+
+/// 缩写不能是更长英文单词的一部分（“jsbin” 不算 “sb”）
+fn contains_pinyin_abbr(haystack: &[char], needle: &[char]) -> bool {
+    if needle.len() > haystack.len() {
+        return false;
+    }
+    let is_letter = |i: Option<usize>| i.and_then(|i| haystack.get(i)).is_some_and(|c| c.is_ascii_lowercase());
+    (0..=haystack.len() - needle.len()).any(|start| {
+        let end = start + needle.len();
+        haystack[start..end].iter().zip(needle).all(|(a, b)| pinyin_eq(*a, *b))
+            && !(is_letter(Some(start)) && is_letter(start.checked_sub(1)))
+            && !(is_letter(Some(end - 1)) && is_letter(Some(end)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_common_traditional_characters() {
+        for (t, s) in [('個', '个'), ('動', '动'), ('說', '说'), ('後', '后'), ('們', '们'), ('髮', '发')] {
+            assert_eq!(T2S.get(&t), Some(&s), "{t}");
+        }
+        // 日文异体与本身就是规范简体的字不转换
+        for c in ['勅', '喰', '俱', '著'] {
+            assert_eq!(T2S.get(&c), None, "{c}");
+        }
+        // 对照表里没有链式映射（单次查表即为最终结果）
+        assert!(T2S.values().all(|s| !T2S.contains_key(s)));
+    }
+
+    #[test]
+    fn matches_traditional_text_in_normalized_mode() {
+        let filter = KeywordFilter::compile(&["一个".to_string()], &[], true, false).unwrap();
+        assert!(filter.find_match("來一個！").is_some());
+        assert_eq!(normalize("說 後"), "说后");
+    }
+
+    fn pinyin_filter(keywords: &[&str]) -> KeywordFilter {
+        let keywords = keywords.iter().map(|k| k.to_string()).collect::<Vec<_>>();
+        KeywordFilter::compile(&keywords, &[], true, true).unwrap()
+    }
+
+    #[test]
+    fn matches_homophones_and_initials() {
+        let filter = pinyin_filter(&["傻逼"]);
+        for text in ["你个沙比", "纯纯傻比", "sb主播", "傻b", "煞笔吧"] {
+            assert!(filter.find_match(text).is_some(), "{text}");
+        }
+        // 汉字之间不按首字母相等，英文单词中间的字母不算缩写
+        for text in ["是不是", "jsbin", "说吧"] {
+            assert!(filter.find_match(text).is_none(), "{text}");
+        }
+        // 单字关键词不做拼音匹配
+        let filter = pinyin_filter(&["死"]);
+        assert!(filter.find_match("四个").is_none());
+        // 关闭拼音匹配时同音字不命中
+        let filter = KeywordFilter::compile(&["傻逼".to_string()], &[], true, false).unwrap();
+        assert!(filter.find_match("沙比").is_none());
+    }
+
+    #[test]
+    fn matches_regex_fullwidth_and_spaced_text() {
+        let filter = KeywordFilter::compile(&["加微信".to_string()], &[r"v[0-9]{3,}".to_string()], true, false).unwrap();
+        assert!(filter.find_match("加 微.信 领福利").is_some());
+        // 正则同时匹配原文与归一化后的文本：全角数字、大小写
+        assert!(filter.find_match("V１２３４").is_some());
+        assert!(filter.find_match("ｖ 8 8 8").is_some());
+        assert!(filter.find_match("正常弹幕 v1").is_none());
+
+        // 不归一化时只做原文匹配
+        let raw = KeywordFilter::compile(&["加微信".to_string()], &[r"v[0-9]{3,}".to_string()], false, false).unwrap();
+        assert!(raw.find_match("加 微.信").is_none());
+        assert!(raw.find_match("V１２３４").is_none());
+        assert!(raw.find_match("v123").is_some());
+
+        assert!(KeywordFilter::compile(&[], &["(".to_string()], true, false).is_err());
+    }
+}
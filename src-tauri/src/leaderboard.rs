@@ -0,0 +1,240 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tokio::sync::RwLock;
+
+use crate::live_event::LiveEvent;
+use crate::sse_server::AppState;
+
+// 定期推送 {"type":"leaderboard"} 的间隔（只在有变化时推送）
+const LEADERBOARD_PUSH_INTERVAL: Duration = Duration::from_secs(30);
+pub const LEADERBOARD_DEFAULT_LIMIT: usize = 10;
+const LEADERBOARD_MAX_LIMIT: usize = 100;
+
+/// 本场（应用启动或手动重置以来）按用户聚合的发言数、礼物价值与醒目留言总额
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserStats {
+    pub uid: Option<u64>,
+    pub user: String,
+    pub face_url: Option<String>,
+    pub message_count: u64,
+    pub gift_count: u64,
+    // 付费礼物 + 大航海（金瓜子，1000 = 1 元）
+    pub gift_value: u64,
+    pub super_chat_count: u64,
+    // 醒目留言总额（元）
+    pub super_chat_total: u64,
+    // 总贡献（金瓜子）= giftValue + superChatTotal * 1000
+    pub total_value: u64,
+    pub last_seen: u64,
+}
+
+#[derive(Default)]
+struct Leaderboard {
+    // 有 uid 时按 uid 聚合，免登录直连拿不到 uid（为 0）时按用户名聚合
+    users: HashMap<String, UserStats>,
+    started_at: u64,
+    // 上次推送后是否有变化
+    dirty: bool,
+}
+
+static LEADERBOARD: Lazy<Arc<RwLock<Leaderboard>>> = Lazy::new(|| {
+    Arc::new(RwLock::new(Leaderboard {
+        started_at: now_ms(),
+        ..Leaderboard::default()
+    }))
+});
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardSnapshot {
+    pub session_start: u64,
+    pub total_users: usize,
+    // 发言最多
+    pub top_chatters: Vec<UserStats>,
+    // 贡献最多（礼物 + 大航海 + 醒目留言）
+    pub top_supporters: Vec<UserStats>,
+    // 醒目留言总额最多
+    pub top_super_chats: Vec<UserStats>,
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+impl Leaderboard {
+    fn record(&mut self, event: &LiveEvent, now: u64) {
+        // 只统计弹幕/礼物/大航海/醒目留言，互动事件不计
+        if !matches!(
+            event,
+            LiveEvent::Danmu(_) | LiveEvent::Gift(_) | LiveEvent::GuardBuy(_) | LiveEvent::SuperChat(_)
+        ) {
+            return;
+        }
+
+        let common = event.common();
+        let uid = common.user.uid.filter(|uid| *uid != 0);
+        let key = match uid {
+            Some(uid) => format!("uid:{uid}"),
+            None => format!("name:{}", common.user.name),
+        };
+
+        let stats = self.users.entry(key).or_default();
+        match event {
+            LiveEvent::Danmu(_) => stats.message_count += 1,
+            LiveEvent::Gift(g) => {
+                stats.gift_count += g.gift_num;
+                // 银瓜子（免费）礼物只计数量，不计价值
+                if g.coin_type == "gold" {
+                    stats.gift_value += g.total_coin;
+                }
+            }
+            LiveEvent::GuardBuy(g) => stats.gift_value += g.price * g.num,
+            LiveEvent::SuperChat(sc) => {
+                stats.super_chat_count += 1;
+                stats.super_chat_total += sc.price;
+            }
+            _ => {}
+        }
+        // 用户名/头像以最近一次为准（用户可能改名）
+        stats.uid = uid;
+        stats.user = common.user.name.clone();
+        if common.user.face_url.is_some() {
+            stats.face_url = common.user.face_url.clone();
+        }
+        stats.total_value = stats.gift_value + stats.super_chat_total * 1000;
+        stats.last_seen = now;
+        self.dirty = true;
+    }
+
+    fn snapshot(&self, limit: usize) -> LeaderboardSnapshot {
+        let limit = limit.clamp(1, LEADERBOARD_MAX_LIMIT);
+        let top_by = |key: fn(&UserStats) -> u64| {
+            let mut list = self.users.values().filter(|s| key(s) > 0).cloned().collect::<Vec<_>>();
+            // 相同时先达到的排前面
+            list.sort_by(|a, b| key(b).cmp(&key(a)).then(a.last_seen.cmp(&b.last_seen)));
+            list.truncate(limit);
+            list
+        };
+        LeaderboardSnapshot {
+            session_start: self.started_at,
+            total_users: self.users.len(),
+            top_chatters: top_by(|s| s.message_count),
+            top_supporters: top_by(|s| s.total_value),
+            top_super_chats: top_by(|s| s.super_chat_total),
+        }
+    }
+
+    fn reset(&mut self, now: u64) {
+        self.users.clear();
+        self.started_at = now;
+        self.dirty = true;
+    }
+}
+
+pub async fn record_event(event: &LiveEvent) {
+    LEADERBOARD.write().await.record(event, now_ms());
+}
+
+pub async fn snapshot(limit: usize) -> LeaderboardSnapshot {
+    LEADERBOARD.read().await.snapshot(limit)
+}
+
+/// 清空本场统计（新开一场直播时调用）
+pub async fn reset() {
+    LEADERBOARD.write().await.reset(now_ms());
+}
+
+/// 随 SSE 服务运行：有变化时定期推送 {"type":"leaderboard", ...}，供结束画面/看板使用
+pub async fn run_broadcast_loop(state: Arc<AppState>, mut shutdown: tokio::sync::watch::Receiver<bool>) {
+    let mut interval = tokio::time::interval(LEADERBOARD_PUSH_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = shutdown.changed() => break,
+            _ = interval.tick() => {
+                let dirty = std::mem::take(&mut LEADERBOARD.write().await.dirty);
+                if !dirty {
+                    continue;
+                }
+                let snapshot = snapshot(LEADERBOARD_DEFAULT_LIMIT).await;
+                let mut msg = serde_json::to_value(snapshot).unwrap_or_default();
+                if let Some(map) = msg.as_object_mut() {
+                    map.insert("type".to_string(), serde_json::Value::String("leaderboard".to_string()));
+                }
+                crate::sse_server::send_to_all_connections(&state, msg).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 测试事件只写关心的字段，其余公共字段补默认值
+    fn event(json: &str) -> LiveEvent {
+        let mut val: serde_json::Value = serde_json::from_str(json).unwrap();
+        let obj = val.as_object_mut().unwrap();
+        for (key, default) in [
+            ("color", serde_json::json!("#ffffff")),
+            ("size", serde_json::json!(32)),
+            ("time", serde_json::json!(0)),
+            ("timestamp", serde_json::json!(0)),
+            ("timestampText", serde_json::json!("")),
+        ] {
+            obj.entry(key).or_insert(default);
+        }
+        serde_json::from_value(val).unwrap()
+    }
+
+    #[test]
+    fn aggregates_per_user_and_ranks_by_value() {
+        let mut lb = Leaderboard::default();
+        let events = [
+            r#"{"type":"danmu","text":"晚上好","user":"甲","uid":1}"#,
+            r#"{"type":"danmu","text":"来了","user":"甲","uid":1}"#,
+            r#"{"type":"danmu","text":"打卡","user":"匿名观众"}"#,
+            r#"{"type":"gift","text":"","user":"乙","uid":2,"giftName":"小心心","giftNum":5,"coinType":"silver","price":0,"totalCoin":0}"#,
+            r#"{"type":"gift","text":"","user":"乙","uid":2,"giftName":"牛哇","giftNum":10,"coinType":"gold","price":100,"totalCoin":1000}"#,
+            r#"{"type":"guardBuy","text":"","user":"丙","uid":3,"giftName":"舰长","num":1,"price":198000,"guardLevel":3}"#,
+            r#"{"type":"superChat","text":"","user":"甲改名","uid":1,"message":"加油","price":50,"duration":60}"#,
+            r#"{"type":"enter","text":"进入直播间","user":"丁","uid":4}"#,
+        ];
+        for (i, json) in events.iter().enumerate() {
+            lb.record(&event(json), i as u64);
+        }
+
+        let snap = lb.snapshot(10);
+        // 互动事件不计；uid 为空时按用户名聚合
+        assert_eq!(snap.total_users, 4);
+        let chatters = snap.top_chatters.iter().map(|s| (s.user.as_str(), s.message_count)).collect::<Vec<_>>();
+        assert_eq!(chatters, [("甲改名", 2), ("匿名观众", 1)]);
+
+        let supporters = snap.top_supporters.iter().map(|s| (s.user.as_str(), s.total_value)).collect::<Vec<_>>();
+        assert_eq!(supporters, [("丙", 198_000), ("甲改名", 50_000), ("乙", 1000)]);
+        // 银瓜子礼物只计数量
+        let yi = &snap.top_supporters[2];
+        assert_eq!((yi.gift_count, yi.gift_value), (15, 1000));
+        assert_eq!(snap.top_super_chats[0].super_chat_total, 50);
+
+        assert_eq!(lb.snapshot(1).top_supporters.len(), 1);
+    }
+
+    #[test]
+    fn reset_starts_a_new_session() {
+        let mut lb = Leaderboard::default();
+        lb.record(&event(r#"{"type":"danmu","text":"晚上好","user":"甲","uid":1}"#), 1);
+        lb.dirty = false;
+        lb.reset(100);
+        let snap = lb.snapshot(10);
+        assert_eq!((snap.session_start, snap.total_users), (100, 0));
+        assert!(snap.top_chatters.is_empty());
+        // 清空也要推送一次
+        assert!(lb.dirty);
+    }
+}
@@ -12,7 +12,23 @@ use tokio::sync::RwLock;
 use tauri::Manager;
 
 mod sse_server;
+pub mod analytics;
 pub mod bili_websocket_client;
+pub mod dedup;
+pub mod error;
+pub mod filter_rules;
+pub mod filter_test;
+pub mod history;
+pub mod keyword_filter;
+pub mod leaderboard;
+pub mod live_event;
+pub mod live_source;
+pub mod packet_log;
+pub mod replay_source;
+pub mod transcript;
+pub mod twitch_client;
+
+use error::AppError;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -90,17 +106,17 @@ fn load_style_profile(app_handle: &tauri::AppHandle) -> StyleProfile {
     StyleProfile::default()
 }
 
-fn save_style_profile(app_handle: &tauri::AppHandle, profile: &StyleProfile) -> Result<(), String> {
-    let path = style_profile_path(app_handle).ok_or_else(|| "无法获取配置目录".to_string())?;
+fn save_style_profile(app_handle: &tauri::AppHandle, profile: &StyleProfile) -> Result<(), AppError> {
+    let path = style_profile_path(app_handle).ok_or_else(|| AppError::Io("无法获取配置目录".to_string()))?;
     if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).map_err(|e| format!("创建配置目录失败: {e}"))?;
+        fs::create_dir_all(parent).map_err(|e| AppError::Io(format!("创建配置目录失败: {e}")))?;
     }
-    let bytes = serde_json::to_vec_pretty(profile).map_err(|e| format!("序列化样式配置失败: {e}"))?;
-    fs::write(&path, bytes).map_err(|e| format!("写入样式配置失败: {e}"))?;
+    let bytes = serde_json::to_vec_pretty(profile).map_err(|e| AppError::Io(format!("序列化样式配置失败: {e}")))?;
+    fs::write(&path, bytes).map_err(|e| AppError::Io(format!("写入样式配置失败: {e}")))?;
     Ok(())
 }
 
-async fn broadcast_config_to_sse(config: sse_server::Config) -> Result<(), String> {
+async fn broadcast_config_to_sse(config: sse_server::Config) -> Result<(), AppError> {
     if let Some(state) = get_sse_state().await {
         *state.config.write().await = config.clone();
         let msg = serde_json::json!({
@@ -110,21 +126,30 @@ async fn broadcast_config_to_sse(config: sse_server::Config) -> Result<(), Strin
         sse_server::send_to_all_connections(&state, msg).await;
         Ok(())
     } else {
-        Err("SSE服务器未启动".to_string())
+        Err(AppError::SseNotRunning)
     }
 }
 
-pub async fn apply_style_to_sse_message(mut val: serde_json::Value) -> serde_json::Value {
-    let Some(obj) = val.as_object_mut() else {
-        return val;
-    };
-    let Some(msg_type) = obj.get("type").and_then(|v| v.as_str()) else {
-        return val;
-    };
-    if msg_type == "config" || msg_type == "ping" {
-        return val;
-    }
-
+/// 样式合成后的 SSE 消息：事件字段 + 明确写入的样式字段（优先级高于 preview.html 的 defaultConfig）
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StyledEvent {
+    #[serde(flatten)]
+    event: live_event::LiveEvent,
+    font_size: u32,
+    stroke_color: String,
+    stroke_width: u32,
+    typing_speed: u32,
+    display_duration: u64,
+    fade_duration: u64,
+    shake_amplitude: f64,
+    random_tilt: f64,
+}
+
+pub async fn apply_style_to_sse_message(mut event: live_event::LiveEvent) -> serde_json::Value {
+    use live_event::{GuardLevel, LiveEvent};
+
+    let msg_type = event.kind();
     let profile = STYLE_PROFILE.read().await;
 
     // 合成策略：
@@ -136,12 +161,8 @@ pub async fn apply_style_to_sse_message(mut val: serde_json::Value) -> serde_jso
         danmu_effective = danmu_cfg.clone();
     }
 
-    let mut effective = if msg_type == "danmu" {
-        danmu_effective.clone()
-    } else {
-        // 其它类型默认继承 danmu
-        danmu_effective.clone()
-    };
+    // 其它类型默认继承 danmu
+    let mut effective = danmu_effective.clone();
 
     if msg_type != "danmu" {
         if let Some(type_cfg) = profile.by_type.get(msg_type) {
@@ -161,12 +182,9 @@ pub async fn apply_style_to_sse_message(mut val: serde_json::Value) -> serde_jso
         effective.stroke_width = cfg.stroke_width;
     };
 
-    if msg_type == "danmu" {
-        let has_own_medal = obj
-            .get("hasOwnMedal")
-            .and_then(|v| v.as_bool())
-            .unwrap_or(false);
-        if has_own_medal {
+    if let LiveEvent::Danmu(danmu) = &event {
+        let common = &danmu.common;
+        if common.medal.has_own_medal {
             if let Some(medal_cfg) = profile.own_medal.as_ref() {
                 // 高亮层：只覆盖“视觉强调”字段
                 apply_visual_overlay(medal_cfg);
@@ -175,45 +193,24 @@ pub async fn apply_style_to_sse_message(mut val: serde_json::Value) -> serde_jso
 
         // 舰队高亮：也是普通弹幕的“子类高亮”
         // blivedm/web.py: privilege_type: 0非舰队, 1总督, 2提督, 3舰长
-        let guard_level = obj
-            .get("guardLevel")
-            .and_then(|v| v.as_i64())
-            .unwrap_or(0);
-        match guard_level {
-            1 => {
-                if let Some(cfg) = profile.guard_governor.as_ref() {
-                    apply_visual_overlay(cfg);
-                }
-            }
-            2 => {
-                if let Some(cfg) = profile.guard_admiral.as_ref() {
-                    apply_visual_overlay(cfg);
-                }
-            }
-            3 => {
-                if let Some(cfg) = profile.guard_captain.as_ref() {
-                    apply_visual_overlay(cfg);
-                }
-            }
-            _ => {}
+        let guard_cfg = match common.guard_level {
+            GuardLevel::Governor => profile.guard_governor.as_ref(),
+            GuardLevel::Admiral => profile.guard_admiral.as_ref(),
+            GuardLevel::Captain => profile.guard_captain.as_ref(),
+            GuardLevel::None => None,
+        };
+        if let Some(cfg) = guard_cfg {
+            apply_visual_overlay(cfg);
         }
 
-        let is_streamer = obj
-            .get("isStreamer")
-            .and_then(|v| v.as_bool())
-            .unwrap_or(false);
-        if is_streamer {
+        if common.user.is_streamer {
             if let Some(cfg) = profile.streamer.as_ref() {
                 // 主播/本人：同样只做视觉强调覆盖
                 apply_visual_overlay(cfg);
             }
         }
 
-        let is_moderator = obj
-            .get("isModerator")
-            .and_then(|v| v.as_bool())
-            .unwrap_or(false);
-        if is_moderator {
+        if common.user.is_moderator {
             if let Some(mod_cfg) = profile.moderator.as_ref() {
                 // 房管优先级更高：同样只做视觉强调覆盖
                 apply_visual_overlay(mod_cfg);
@@ -221,59 +218,24 @@ pub async fn apply_style_to_sse_message(mut val: serde_json::Value) -> serde_jso
         }
     }
 
-    // 明确写入样式字段，优先级高于 preview.html 的 defaultConfig
-    obj.insert(
-        "fontSize".to_string(),
-        serde_json::Value::Number(serde_json::Number::from(effective.font_size)),
-    );
-    // 如果样式配置里 color/strokeColor 为 null，则回退到 websocket 原始颜色（消息里的 color）。
-    let ws_color = obj
-        .get("color")
-        .and_then(|v| v.as_str())
-        .unwrap_or("#ffffff")
-        .to_string();
-
-    let final_color = effective.color.clone().unwrap_or_else(|| ws_color.clone());
-    let final_stroke_color = effective
-        .stroke_color
-        .clone()
-        .unwrap_or_else(|| ws_color.clone());
-
-    obj.insert("color".to_string(), serde_json::Value::String(final_color));
-    obj.insert(
-        "strokeColor".to_string(),
-        serde_json::Value::String(final_stroke_color),
-    );
-    obj.insert(
-        "strokeWidth".to_string(),
-        serde_json::Value::Number(serde_json::Number::from(effective.stroke_width)),
-    );
-    obj.insert(
-        "typingSpeed".to_string(),
-        serde_json::Value::Number(serde_json::Number::from(effective.typing_speed)),
-    );
-    obj.insert(
-        "displayDuration".to_string(),
-        serde_json::Value::Number(serde_json::Number::from(effective.display_duration)),
-    );
-    obj.insert(
-        "fadeDuration".to_string(),
-        serde_json::Value::Number(serde_json::Number::from(effective.fade_duration)),
-    );
-    obj.insert(
-        "shakeAmplitude".to_string(),
-        serde_json::Value::Number(
-            serde_json::Number::from_f64(effective.shake_amplitude).unwrap_or_else(|| 0.into()),
-        ),
-    );
-    obj.insert(
-        "randomTilt".to_string(),
-        serde_json::Value::Number(
-            serde_json::Number::from_f64(effective.random_tilt).unwrap_or_else(|| 0.into()),
-        ),
-    );
-
-    val
+    // 如果样式配置里 color/strokeColor 为 null，则回退到 websocket 原始颜色（事件里的 color）。
+    let common = event.common_mut();
+    let ws_color = common.color.clone();
+    common.color = effective.color.clone().unwrap_or_else(|| ws_color.clone());
+    let stroke_color = effective.stroke_color.clone().unwrap_or(ws_color);
+
+    let styled = StyledEvent {
+        event,
+        font_size: effective.font_size,
+        stroke_color,
+        stroke_width: effective.stroke_width,
+        typing_speed: effective.typing_speed,
+        display_duration: effective.display_duration,
+        fade_duration: effective.fade_duration,
+        shake_amplitude: effective.shake_amplitude,
+        random_tilt: effective.random_tilt,
+    };
+    serde_json::to_value(styled).unwrap_or(serde_json::Value::Null)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -298,7 +260,15 @@ pub fn run() {
             // 启动SSE服务器
             let app_handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
+                // 历史记录库：打开失败只影响历史查询，不影响转发
+                if let Ok(dir) = app_handle.path().app_config_dir() {
+                    if let Err(e) = history::open_history_db(&dir.join("yjdanmu-history.sqlite3")).await {
+                        eprintln!("[History] {e}");
+                    }
+                }
+
                 let settings = load_general_settings(&app_handle).unwrap_or_default();
+                bili_websocket_client::set_muted_users(settings.muted_users.clone()).await;
                 if let Err(e) = start_or_restart_sse_server(app_handle, settings).await {
                     eprintln!("[SSE] 启动失败: {e}");
                 }
@@ -324,12 +294,26 @@ pub fn run() {
             get_room_style_profile,
             set_room_style_profile,
             send_danmu,
+            retract_danmu,
             send_config,
             get_status,
             open_in_browser,
             toggle_always_on_top,
             connect_websocket,
-            disconnect_websocket
+            disconnect_websocket,
+            list_websocket_connections,
+            start_packet_recording,
+            stop_packet_recording,
+            start_replay,
+            list_transcripts,
+            export_transcript,
+            query_history,
+            get_leaderboard,
+            reset_leaderboard,
+            test_danmu_filter,
+            mute_user,
+            unmute_user,
+            list_muted_users
         ])
         .run(context)
         .expect("error while running tauri application");
@@ -347,6 +331,22 @@ pub struct GeneralSettings {
     pub danmu_filter: bili_websocket_client::DanmuFilterConfig,
     #[serde(default)]
     pub render_settings: sse_server::RenderConfig,
+    /// 互动事件（进房/关注/分享/点赞/看过）转发开关
+    #[serde(default)]
+    pub interact_events: bili_websocket_client::InteractEventConfig,
+    /// 心跳看门狗超时（秒）：超时未收到服务端数据则判定连接僵死并重连，0 表示关闭
+    #[serde(default = "default_heartbeat_timeout_secs")]
+    pub heartbeat_timeout_secs: u64,
+    /// 场次记录：把推送的事件追加到配置目录 transcripts/，用于导出字幕/表格
+    #[serde(default)]
+    pub transcript_enabled: bool,
+    /// 临时禁言列表（由禁言命令与 /api/mute 维护，设置页不直接编辑）
+    #[serde(default)]
+    pub muted_users: Vec<bili_websocket_client::MutedUser>,
+}
+
+fn default_heartbeat_timeout_secs() -> u64 {
+    60
 }
 
 impl Default for GeneralSettings {
@@ -360,6 +360,10 @@ impl Default for GeneralSettings {
             default_max_reconnect_attempts: 5,
             danmu_filter: bili_websocket_client::DanmuFilterConfig::default(),
             render_settings: sse_server::RenderConfig::default(),
+            interact_events: bili_websocket_client::InteractEventConfig::default(),
+            heartbeat_timeout_secs: default_heartbeat_timeout_secs(),
+            transcript_enabled: false,
+            muted_users: Vec::new(),
         }
     }
 }
@@ -371,6 +375,8 @@ struct SseRuntime {
     bind_addrs: Vec<SocketAddr>,
     join_handles: Vec<tauri::async_runtime::JoinHandle<()>>,
     settings: GeneralSettings,
+    // 用于在 SSE 接口里（如 /api/mute）回写设置文件
+    app_handle: Option<tauri::AppHandle>,
 }
 
 static SSE_RUNTIME: Lazy<Arc<RwLock<SseRuntime>>> = Lazy::new(|| Arc::new(RwLock::new(SseRuntime::default())));
@@ -386,16 +392,27 @@ fn load_general_settings(app_handle: &tauri::AppHandle) -> Option<GeneralSetting
     serde_json::from_slice::<GeneralSettings>(&bytes).ok()
 }
 
-fn save_general_settings(app_handle: &tauri::AppHandle, settings: &GeneralSettings) -> Result<(), String> {
-    let path = settings_path(app_handle).ok_or_else(|| "无法获取配置目录".to_string())?;
+fn save_general_settings(app_handle: &tauri::AppHandle, settings: &GeneralSettings) -> Result<(), AppError> {
+    let path = settings_path(app_handle).ok_or_else(|| AppError::Io("无法获取配置目录".to_string()))?;
     if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).map_err(|e| format!("创建配置目录失败: {e}"))?;
+        fs::create_dir_all(parent).map_err(|e| AppError::Io(format!("创建配置目录失败: {e}")))?;
     }
-    let bytes = serde_json::to_vec_pretty(settings).map_err(|e| format!("序列化设置失败: {e}"))?;
-    fs::write(&path, bytes).map_err(|e| format!("写入设置失败: {e}"))?;
+    let bytes = serde_json::to_vec_pretty(settings).map_err(|e| AppError::Io(format!("序列化设置失败: {e}")))?;
+    fs::write(&path, bytes).map_err(|e| AppError::Io(format!("写入设置失败: {e}")))?;
     Ok(())
 }
 
+/// 禁言列表变化后写回设置文件
+pub(crate) async fn persist_muted_users() -> Result<(), AppError> {
+    let muted_users = bili_websocket_client::list_muted_users().await;
+    let mut rt = SSE_RUNTIME.write().await;
+    rt.settings.muted_users = muted_users;
+    match rt.app_handle.clone() {
+        Some(app_handle) => save_general_settings(&app_handle, &rt.settings),
+        None => Ok(()),
+    }
+}
+
 pub async fn get_sse_state() -> Option<Arc<sse_server::AppState>> {
     SSE_RUNTIME.read().await.state.clone()
 }
@@ -419,11 +436,24 @@ async fn stop_sse_server() {
     }
 }
 
-async fn start_or_restart_sse_server(app_handle: tauri::AppHandle, settings: GeneralSettings) -> Result<String, String> {
+async fn apply_transcript_settings(app_handle: &tauri::AppHandle, settings: &GeneralSettings) {
+    let dir = app_handle
+        .path()
+        .app_config_dir()
+        .ok()
+        .map(|dir| transcript::transcripts_dir(&dir));
+    transcript::set_transcript_config(dir, settings.transcript_enabled).await;
+}
+
+async fn start_or_restart_sse_server(app_handle: tauri::AppHandle, mut settings: GeneralSettings) -> Result<String, AppError> {
     use tokio::net::TcpListener;
     use std::io::ErrorKind;
     use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
+    // 禁言列表以运行时为准（设置页提交的内容里没有它）
+    settings.muted_users = bili_websocket_client::list_muted_users().await;
+    SSE_RUNTIME.write().await.app_handle = Some(app_handle.clone());
+
     let addr_v4 = SocketAddr::new(
         IpAddr::V4(if settings.sse_public { Ipv4Addr::UNSPECIFIED } else { Ipv4Addr::LOCALHOST }),
         settings.sse_port,
@@ -465,7 +495,10 @@ async fn start_or_restart_sse_server(app_handle: tauri::AppHandle, settings: Gen
             // 应用 WS debug/过滤配置（不需要重启）
             drop(rt);
             bili_websocket_client::set_ws_debug_enabled(settings.ws_debug).await;
-            bili_websocket_client::set_danmu_filter_config(settings.danmu_filter.clone()).await;
+            bili_websocket_client::set_danmu_filter_config(settings.danmu_filter.clone()).await?;
+            bili_websocket_client::set_interact_event_config(settings.interact_events.clone()).await;
+            bili_websocket_client::set_heartbeat_timeout_secs(settings.heartbeat_timeout_secs).await;
+            apply_transcript_settings(&app_handle, &settings).await;
 
             // 更新 runtime 记录
             let mut rt2 = SSE_RUNTIME.write().await;
@@ -477,12 +510,17 @@ async fn start_or_restart_sse_server(app_handle: tauri::AppHandle, settings: Gen
         }
     }
 
+    // 过滤配置（正则）无效时不重启，保留正在运行的服务
+    bili_websocket_client::set_danmu_filter_config(settings.danmu_filter.clone()).await?;
+
     // 需要真正重启：先停旧的并等待退出
     stop_sse_server().await;
 
     // 应用 WS debug/过滤配置
     bili_websocket_client::set_ws_debug_enabled(settings.ws_debug).await;
-    bili_websocket_client::set_danmu_filter_config(settings.danmu_filter.clone()).await;
+    bili_websocket_client::set_interact_event_config(settings.interact_events.clone()).await;
+    bili_websocket_client::set_heartbeat_timeout_secs(settings.heartbeat_timeout_secs).await;
+    apply_transcript_settings(&app_handle, &settings).await;
 
     let state = Arc::new(sse_server::AppState {
         sse_connections: Arc::new(RwLock::new(std::collections::HashMap::new())),
@@ -497,14 +535,14 @@ async fn start_or_restart_sse_server(app_handle: tauri::AppHandle, settings: Gen
     let listener_v4 = TcpListener::bind(addr_v4)
         .await
         .map_err(|e| {
-            if e.kind() == ErrorKind::AddrInUse {
+            AppError::SseBindFailed(if e.kind() == ErrorKind::AddrInUse {
                 format!(
                     "SSE 端口绑定失败：端口 {} 已被占用。请在【通用设置】里更换端口，或关闭占用该端口的程序。原始错误: {e}",
                     settings.sse_port
                 )
             } else {
                 format!("SSE 端口绑定失败: {e}")
-            }
+            })
         })?;
 
     // IPv6 监听（用于让 localhost/::1 访问不再产生 IPv6->IPv4 回退等待）
@@ -532,6 +570,12 @@ async fn start_or_restart_sse_server(app_handle: tauri::AppHandle, settings: Gen
         }));
     }
 
+    // 排行榜定期推送，随 SSE 服务一起停止
+    join_handles.push(tauri::async_runtime::spawn(leaderboard::run_broadcast_loop(
+        state.clone(),
+        shutdown_rx.clone(),
+    )));
+
     if let Some(listener_v6) = listener_v6 {
         let app = sse_server::create_app(state.clone());
         let mut rx = shutdown_rx.clone();
@@ -558,7 +602,7 @@ async fn start_or_restart_sse_server(app_handle: tauri::AppHandle, settings: Gen
 }
 
 #[tauri::command]
-async fn start_sse_server_cmd() -> Result<String, String> {
+async fn start_sse_server_cmd() -> Result<String, AppError> {
     let rt = SSE_RUNTIME.read().await;
     if let Some(addr) = rt.bind_addrs.first().copied() {
         Ok(format!("SSE服务器运行中: http://{}", addr))
@@ -568,7 +612,7 @@ async fn start_sse_server_cmd() -> Result<String, String> {
 }
 
 #[tauri::command]
-async fn get_general_settings(window: tauri::Window) -> Result<serde_json::Value, String> {
+async fn get_general_settings(window: tauri::Window) -> Result<serde_json::Value, AppError> {
     let app_handle = window.app_handle();
     let disk = load_general_settings(&app_handle).unwrap_or_default();
     let rt = SSE_RUNTIME.read().await;
@@ -579,13 +623,13 @@ async fn get_general_settings(window: tauri::Window) -> Result<serde_json::Value
 }
 
 #[tauri::command]
-async fn set_general_settings(window: tauri::Window, settings: GeneralSettings) -> Result<String, String> {
+async fn set_general_settings(window: tauri::Window, settings: GeneralSettings) -> Result<String, AppError> {
     let app_handle = window.app_handle();
     start_or_restart_sse_server(app_handle.clone(), settings).await
 }
 
 #[tauri::command]
-async fn send_danmu(text: String, custom_data: Option<serde_json::Value>) -> Result<String, String> {
+async fn send_danmu(text: String, custom_data: Option<serde_json::Value>) -> Result<String, AppError> {
     if let Some(state) = get_sse_state().await {
         // 构建弹幕数据，确保基本字段存在
         let mut base_data = serde_json::json!({
@@ -626,11 +670,11 @@ async fn send_danmu(text: String, custom_data: Option<serde_json::Value>) -> Res
         
         // 解析弹幕数据
         let danmu_data: sse_server::DanmuData = serde_json::from_value(base_data)
-            .map_err(|e| format!("解析弹幕数据失败: {}", e))?;
+            .map_err(|e| AppError::InvalidArgument(format!("解析弹幕数据失败: {}", e)))?;
         
         // 发送弹幕
         let json_data = serde_json::to_value(danmu_data)
-            .map_err(|e| format!("序列化弹幕数据失败: {}", e))?;
+            .map_err(|e| AppError::Io(format!("序列化弹幕数据失败: {}", e)))?;
         
         sse_server::send_to_all_connections(&state, json_data).await;
         
@@ -646,12 +690,27 @@ async fn send_danmu(text: String, custom_data: Option<serde_json::Value>) -> Res
         
         Ok("弹幕发送成功".to_string())
     } else {
-        Err("SSE服务器未启动".to_string())
+        Err(AppError::SseNotRunning)
     }
 }
 
 #[tauri::command]
-async fn send_config(config: sse_server::Config) -> Result<String, String> {
+async fn retract_danmu(ids: Vec<String>) -> Result<String, AppError> {
+    let ids: Vec<String> = ids.into_iter().filter(|id| !id.is_empty()).collect();
+    if ids.is_empty() {
+        return Err(AppError::InvalidArgument("缺少要撤回的 id".to_string()));
+    }
+    if let Some(state) = get_sse_state().await {
+        let count = ids.len();
+        sse_server::broadcast_retract(&state, ids).await;
+        Ok(format!("已撤回 {} 条", count))
+    } else {
+        Err(AppError::SseNotRunning)
+    }
+}
+
+#[tauri::command]
+async fn send_config(config: sse_server::Config) -> Result<String, AppError> {
     if let Some(state) = get_sse_state().await {
         // 更新全局配置
         *state.config.write().await = config.clone();
@@ -668,12 +727,12 @@ async fn send_config(config: sse_server::Config) -> Result<String, String> {
         
         Ok("配置更新成功".to_string())
     } else {
-        Err("SSE服务器未启动".to_string())
+        Err(AppError::SseNotRunning)
     }
 }
 
 #[tauri::command]
-async fn get_status() -> Result<serde_json::Value, String> {
+async fn get_status() -> Result<serde_json::Value, AppError> {
     if let Some(state) = get_sse_state().await {
         let connections = state.sse_connections.read().await.len();
         let stats = state.stats.read().await.clone();
@@ -682,6 +741,10 @@ async fn get_status() -> Result<serde_json::Value, String> {
             "connections": connections,
             "danmu_count": stats.danmu_count,
             "last_activity": stats.last_activity,
+            "dropped": stats.dropped,
+            "metrics": bili_websocket_client::list_metrics().await,
+            "packetRecording": bili_websocket_client::packet_recording_status().await,
+            "analytics": analytics::snapshot().await,
             "timestamp": std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
@@ -689,27 +752,32 @@ async fn get_status() -> Result<serde_json::Value, String> {
             "development": false  // 默认不是开发模式
         }))
     } else {
-        Err("SSE服务器未启动".to_string())
+        Err(AppError::SseNotRunning)
     }
 }
 
 #[tauri::command]
-async fn open_in_browser(url: String) -> Result<String, String> {
+async fn open_in_browser(url: String) -> Result<String, AppError> {
     webbrowser::open(&url)
-        .map_err(|e| format!("打开浏览器失败: {}", e))?;
+        .map_err(|e| AppError::Io(format!("打开浏览器失败: {}", e)))?;
     Ok("已在浏览器中打开".to_string())
 }
 
 #[tauri::command]
-async fn toggle_always_on_top(window: tauri::Window) -> Result<bool, String> {
-    let current = window.is_always_on_top().map_err(|e| format!("获取窗口状态失败: {}", e))?;
-    window.set_always_on_top(!current).map_err(|e| format!("设置窗口置顶失败: {}", e))?;
+async fn toggle_always_on_top(window: tauri::Window) -> Result<bool, AppError> {
+    let current = window
+        .is_always_on_top()
+        .map_err(|e| AppError::Io(format!("获取窗口状态失败: {}", e)))?;
+    window
+        .set_always_on_top(!current)
+        .map_err(|e| AppError::Io(format!("设置窗口置顶失败: {}", e)))?;
     Ok(!current)
 }
 
 #[tauri::command]
 async fn connect_websocket(
     window: tauri::Window,
+    connection_id: String,
     room_key: String,
     room_key_type: bili_websocket_client::RoomKeyType,
     reconnect_interval: u64,
@@ -717,7 +785,7 @@ async fn connect_websocket(
     open_live_app_id: Option<i64>,
     open_live_access_key_id: Option<String>,
     open_live_access_key_secret: Option<String>,
-) -> Result<String, String> {
+) -> Result<String, AppError> {
     let app_handle = window.app_handle();
 
     // 连接前下发全局 base 样式，让 preview 立即切换默认样式
@@ -728,6 +796,7 @@ async fn connect_websocket(
 
     bili_websocket_client::connect_websocket(
         app_handle.clone(),
+        connection_id,
         room_key,
         room_key_type,
         reconnect_interval,
@@ -741,20 +810,173 @@ async fn connect_websocket(
 }
 
 #[tauri::command]
-async fn disconnect_websocket() -> Result<String, String> {
-    bili_websocket_client::disconnect_websocket().await.map(|_| "WebSocket断开成功".to_string())
+async fn disconnect_websocket(connection_id: String) -> Result<String, AppError> {
+    bili_websocket_client::disconnect_websocket(&connection_id)
+        .await
+        .map(|_| "WebSocket断开成功".to_string())
+}
+
+#[tauri::command]
+async fn start_packet_recording(
+    window: tauri::Window,
+    connection_id: Option<String>,
+    path: Option<String>,
+) -> Result<bili_websocket_client::PacketRecordingInfo, AppError> {
+    // 未指定路径时写到配置目录 recordings/ 下，按开始时间命名
+    let path = match path.filter(|s| !s.trim().is_empty()) {
+        Some(p) => PathBuf::from(p),
+        None => {
+            let dir = window
+                .app_handle()
+                .path()
+                .app_config_dir()
+                .map_err(|_| AppError::Io("无法获取配置目录".to_string()))?;
+            dir.join("recordings")
+                .join(format!("packets-{}.blpk", chrono::Local::now().format("%Y%m%d-%H%M%S")))
+        }
+    };
+    bili_websocket_client::start_packet_recording(path, connection_id).await
+}
+
+#[tauri::command]
+async fn stop_packet_recording() -> Result<bili_websocket_client::PacketRecordingInfo, AppError> {
+    bili_websocket_client::stop_packet_recording()
+        .await
+        .ok_or_else(|| AppError::InvalidArgument("当前没有在录制".to_string()))
+}
+
+#[tauri::command]
+async fn start_replay(
+    window: tauri::Window,
+    connection_id: String,
+    path: String,
+    speed: Option<f64>,
+) -> Result<String, AppError> {
+    let app_handle = window.app_handle();
+    {
+        let profile = STYLE_PROFILE.read().await;
+        let _ = broadcast_config_to_sse(profile.base.clone()).await;
+    }
+    let speed = speed.unwrap_or(1.0);
+    bili_websocket_client::connect_replay(app_handle.clone(), connection_id, path, speed)
+        .await
+        .map(|_| format!("开始回放（{}x）", speed))
+}
+
+#[tauri::command]
+async fn list_transcripts(window: tauri::Window) -> Result<Vec<transcript::TranscriptInfo>, AppError> {
+    let dir = window
+        .app_handle()
+        .path()
+        .app_config_dir()
+        .map_err(|_| AppError::Io("无法获取配置目录".to_string()))?;
+    Ok(transcript::list_transcripts(&dir).await)
+}
+
+#[tauri::command]
+async fn export_transcript(
+    window: tauri::Window,
+    session: Option<String>,
+    format: transcript::TranscriptFormat,
+    start_ms: Option<u64>,
+    out_path: Option<String>,
+) -> Result<String, AppError> {
+    // 未指定场次时导出最新的一场
+    let session = match session.filter(|s| !s.trim().is_empty()) {
+        Some(s) => PathBuf::from(s),
+        None => {
+            let dir = window
+                .app_handle()
+                .path()
+                .app_config_dir()
+                .map_err(|_| AppError::Io("无法获取配置目录".to_string()))?;
+            let latest = transcript::list_transcripts(&dir)
+                .await
+                .into_iter()
+                .next()
+                .ok_or_else(|| AppError::InvalidArgument("还没有场次记录".to_string()))?;
+            PathBuf::from(latest.path)
+        }
+    };
+    let profile = STYLE_PROFILE.read().await.clone();
+    let out = transcript::export_transcript(
+        &session,
+        format,
+        out_path.filter(|s| !s.trim().is_empty()).map(PathBuf::from),
+        start_ms,
+        &profile,
+    )
+    .await?;
+    Ok(out.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+async fn query_history(query: history::HistoryQuery) -> Result<Vec<history::HistoryEntry>, AppError> {
+    history::query_history(&query).await
+}
+
+#[tauri::command]
+async fn get_leaderboard(limit: Option<usize>) -> Result<leaderboard::LeaderboardSnapshot, AppError> {
+    Ok(leaderboard::snapshot(limit.unwrap_or(leaderboard::LEADERBOARD_DEFAULT_LIMIT)).await)
+}
+
+#[tauri::command]
+async fn mute_user(
+    uid: u64,
+    minutes: u64,
+    user: Option<String>,
+    reason: Option<String>,
+) -> Result<bili_websocket_client::MutedUser, AppError> {
+    let muted = bili_websocket_client::mute_user(uid, minutes, user, reason).await?;
+    persist_muted_users().await?;
+    Ok(muted)
+}
+
+#[tauri::command]
+async fn unmute_user(uid: u64) -> Result<String, AppError> {
+    if !bili_websocket_client::unmute_user(uid).await {
+        return Err(AppError::InvalidArgument(format!("uid {} 未被禁言", uid)));
+    }
+    persist_muted_users().await?;
+    Ok(format!("已解除禁言: {}", uid))
+}
+
+#[tauri::command]
+async fn list_muted_users() -> Result<Vec<bili_websocket_client::MutedUser>, AppError> {
+    Ok(bili_websocket_client::list_muted_users().await)
+}
+
+/// 过滤 dry-run：用候选配置评估样例事件或录制的事件日志，不影响生效配置
+#[tauri::command]
+async fn test_danmu_filter(request: filter_test::FilterTestRequest) -> Result<filter_test::FilterTestReport, AppError> {
+    filter_test::run(request).await
+}
+
+#[tauri::command]
+async fn reset_leaderboard() -> Result<String, AppError> {
+    leaderboard::reset().await;
+    Ok("已清空本场统计".to_string())
+}
+
+#[tauri::command]
+async fn list_websocket_connections() -> Result<Vec<bili_websocket_client::ConnectionInfo>, AppError> {
+    Ok(bili_websocket_client::list_connections().await)
 }
 
 #[tauri::command]
-async fn get_current_room_context() -> Result<serde_json::Value, String> {
+async fn get_current_room_context() -> Result<serde_json::Value, AppError> {
+    // 兼容旧前端：roomKey/roomKeyType 取第一个连接；完整列表见 connections
+    let connections = bili_websocket_client::list_connections().await;
+    let first = connections.first();
     Ok(serde_json::json!({
-        "roomKey": bili_websocket_client::current_room_key().await,
-        "roomKeyType": bili_websocket_client::current_room_key_type().await,
+        "roomKey": first.map(|c| c.room_key.clone()),
+        "roomKeyType": first.map(|c| c.room_key_type),
+        "connections": connections,
     }))
 }
 
 #[tauri::command]
-async fn get_style_profile(_window: tauri::Window) -> Result<serde_json::Value, String> {
+async fn get_style_profile(_window: tauri::Window) -> Result<serde_json::Value, AppError> {
     let profile = STYLE_PROFILE.read().await.clone();
     Ok(serde_json::json!({
         "profile": profile,
@@ -762,7 +984,7 @@ async fn get_style_profile(_window: tauri::Window) -> Result<serde_json::Value,
 }
 
 #[tauri::command]
-async fn set_style_profile(window: tauri::Window, profile: StyleProfile) -> Result<String, String> {
+async fn set_style_profile(window: tauri::Window, profile: StyleProfile) -> Result<String, AppError> {
     let app_handle = window.app_handle();
     {
         *STYLE_PROFILE.write().await = profile.clone();
@@ -775,12 +997,12 @@ async fn set_style_profile(window: tauri::Window, profile: StyleProfile) -> Resu
 }
 
 #[tauri::command]
-async fn get_room_style_profile(_window: tauri::Window, room_key: Option<String>) -> Result<serde_json::Value, String> {
+async fn get_room_style_profile(_window: tauri::Window, room_key: Option<String>) -> Result<serde_json::Value, AppError> {
     let _ = room_key;
     get_style_profile(_window).await
 }
 
 #[tauri::command]
-async fn set_room_style_profile(window: tauri::Window, _room_key: String, profile: StyleProfile) -> Result<String, String> {
+async fn set_room_style_profile(window: tauri::Window, _room_key: String, profile: StyleProfile) -> Result<String, AppError> {
     set_style_profile(window, profile).await
 }
@@ -0,0 +1,323 @@
+use serde::{Deserialize, Serialize};
+
+/// 统一的直播事件模型：各协议的解析结果先转成 LiveEvent，再交给过滤、样式与 SSE 推送。
+/// 序列化结果与旧版手拼 serde_json::Value 的 SSE JSON 保持一致（扁平结构，type 字段区分类型）。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum LiveEvent {
+    Danmu(DanmuEvent),
+    Gift(GiftEvent),
+    SuperChat(SuperChatEvent),
+    GuardBuy(GuardBuyEvent),
+    // 互动事件：进入直播间 / 关注 / 分享 / 点赞
+    Enter(InteractEvent),
+    Follow(InteractEvent),
+    Share(InteractEvent),
+    Like(InteractEvent),
+    // 看过人数变化
+    Watched(WatchedEvent),
+    // 开播 / 下播（开放平台 LIVE_OPEN_PLATFORM_LIVE_START/END）
+    LiveStart(LiveStatusEvent),
+    LiveEnd(LiveStatusEvent),
+    // 醒目留言被删除（携带被删除的 scId 列表；forward_to_sse 中转成 retract 消息，不直接推送）
+    SuperChatDelete(SuperChatDeleteEvent),
+}
+
+impl LiveEvent {
+    /// 与序列化后的 type 字段一致，StyleProfile.by_type 以此为键
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LiveEvent::Danmu(_) => "danmu",
+            LiveEvent::Gift(_) => "gift",
+            LiveEvent::SuperChat(_) => "superChat",
+            LiveEvent::GuardBuy(_) => "guardBuy",
+            LiveEvent::Enter(_) => "enter",
+            LiveEvent::Follow(_) => "follow",
+            LiveEvent::Share(_) => "share",
+            LiveEvent::Like(_) => "like",
+            LiveEvent::Watched(_) => "watched",
+            LiveEvent::LiveStart(_) => "liveStart",
+            LiveEvent::LiveEnd(_) => "liveEnd",
+            LiveEvent::SuperChatDelete(_) => "superChatDelete",
+        }
+    }
+
+    pub fn common(&self) -> &EventCommon {
+        match self {
+            LiveEvent::Danmu(e) => &e.common,
+            LiveEvent::Gift(e) => &e.common,
+            LiveEvent::SuperChat(e) => &e.common,
+            LiveEvent::GuardBuy(e) => &e.common,
+            LiveEvent::Enter(e)
+            | LiveEvent::Follow(e)
+            | LiveEvent::Share(e)
+            | LiveEvent::Like(e) => &e.common,
+            LiveEvent::Watched(e) => &e.common,
+            LiveEvent::LiveStart(e) | LiveEvent::LiveEnd(e) => &e.common,
+            LiveEvent::SuperChatDelete(e) => &e.common,
+        }
+    }
+
+    pub fn common_mut(&mut self) -> &mut EventCommon {
+        match self {
+            LiveEvent::Danmu(e) => &mut e.common,
+            LiveEvent::Gift(e) => &mut e.common,
+            LiveEvent::SuperChat(e) => &mut e.common,
+            LiveEvent::GuardBuy(e) => &mut e.common,
+            LiveEvent::Enter(e)
+            | LiveEvent::Follow(e)
+            | LiveEvent::Share(e)
+            | LiveEvent::Like(e) => &mut e.common,
+            LiveEvent::Watched(e) => &mut e.common,
+            LiveEvent::LiveStart(e) | LiveEvent::LiveEnd(e) => &mut e.common,
+            LiveEvent::SuperChatDelete(e) => &mut e.common,
+        }
+    }
+}
+
+/// 所有事件共有的字段
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventCommon {
+    // 事件 id：预览端按此撤回（retract）已推送的字幕
+    #[serde(default = "new_event_id")]
+    pub id: String,
+    pub text: String,
+    #[serde(flatten)]
+    pub user: UserInfo,
+    // 以下字段缺省时按 EventCommon::new 的默认值补齐，手写的样例事件（/api/filter/test）只需 type 与 text
+    #[serde(default = "default_color")]
+    pub color: String,
+    #[serde(default = "default_size")]
+    pub size: u32,
+    // 兼容字段：事件生成时刻的 Unix 毫秒时间戳
+    #[serde(default = "now_ms")]
+    pub time: u64,
+    // 对齐 blivedm/web.py: timestamp 是 Unix 毫秒时间戳
+    #[serde(default = "now_ms")]
+    pub timestamp: u64,
+    // 仅用于展示的人类可读时间（不要当作 timestamp 使用）
+    #[serde(rename = "timestampText", default)]
+    pub timestamp_text: String,
+    #[serde(flatten)]
+    pub medal: MedalInfo,
+    #[serde(rename = "guardLevel", default)]
+    pub guard_level: GuardLevel,
+    #[serde(flatten)]
+    pub source: EventSource,
+}
+
+impl EventCommon {
+    /// 缺省用户名/颜色/字号与 sse_server::DanmuData 的默认值一致；time_ms 为空时取当前时间
+    pub fn new(text: String, user: Option<String>, time_ms: Option<u64>) -> Self {
+        let now = now_ms();
+        let timestamp = time_ms.unwrap_or(now);
+        Self {
+            id: new_event_id(),
+            text,
+            user: UserInfo {
+                name: user.unwrap_or_else(default_user_name),
+                ..UserInfo::default()
+            },
+            color: default_color(),
+            size: default_size(),
+            time: now,
+            timestamp,
+            timestamp_text: hms_from_unix_ms(timestamp),
+            medal: MedalInfo::default(),
+            guard_level: GuardLevel::None,
+            source: EventSource::default(),
+        }
+    }
+}
+
+fn default_color() -> String {
+    "#ffffff".to_string()
+}
+
+fn default_size() -> u32 {
+    32
+}
+
+fn default_user_name() -> String {
+    "匿名用户".to_string()
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserInfo {
+    #[serde(rename = "user", default = "default_user_name")]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u64>,
+    #[serde(default)]
+    pub face_url: Option<String>,
+    #[serde(rename = "isModerator", default)]
+    pub is_moderator: bool,
+    // 主播/本人（annotate_event 中按主播 uid / face_url 判定）
+    #[serde(rename = "isStreamer", default)]
+    pub is_streamer: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MedalInfo {
+    // 粉丝牌所属主播 uid；未佩戴时为 null
+    #[serde(default)]
+    pub media_ruid: Option<u64>,
+    // 佩戴本房间粉丝牌（media_ruid 与主播 uid 匹配，annotate_event 中判定；开放平台直接由 fans_medal_wearing_status 给出）
+    #[serde(rename = "hasOwnMedal", default)]
+    pub has_own_medal: bool,
+    #[serde(rename = "medalName", default, skip_serializing_if = "Option::is_none")]
+    pub medal_name: Option<String>,
+    #[serde(rename = "medalLevel", default, skip_serializing_if = "Option::is_none")]
+    pub medal_level: Option<u32>,
+}
+
+/// 舰队等级（blivedm/web.py: privilege_type），序列化为数字 0-3
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "u8", into = "u8")]
+pub enum GuardLevel {
+    #[default]
+    None,
+    Governor,
+    Admiral,
+    Captain,
+}
+
+impl From<u8> for GuardLevel {
+    fn from(v: u8) -> Self {
+        match v {
+            1 => GuardLevel::Governor,
+            2 => GuardLevel::Admiral,
+            3 => GuardLevel::Captain,
+            _ => GuardLevel::None,
+        }
+    }
+}
+
+impl From<GuardLevel> for u8 {
+    fn from(v: GuardLevel) -> Self {
+        match v {
+            GuardLevel::None => 0,
+            GuardLevel::Governor => 1,
+            GuardLevel::Admiral => 2,
+            GuardLevel::Captain => 3,
+        }
+    }
+}
+
+/// 事件来源：多房间同时连接时，前端据此区分事件来自哪个房间
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EventSource {
+    #[serde(rename = "connectionId", default, skip_serializing_if = "Option::is_none")]
+    pub connection_id: Option<String>,
+    #[serde(rename = "roomId", default)]
+    pub room_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DanmuEvent {
+    #[serde(flatten)]
+    pub common: EventCommon,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GiftEvent {
+    #[serde(flatten)]
+    pub common: EventCommon,
+    #[serde(rename = "giftName")]
+    pub gift_name: String,
+    #[serde(rename = "giftNum")]
+    pub gift_num: u64,
+    // 瓜子类型：gold 金瓜子（付费）/ silver 银瓜子（免费）
+    #[serde(rename = "coinType")]
+    pub coin_type: String,
+    // 礼物单价（瓜子，1000 金瓜子 = 1 元）
+    pub price: u64,
+    // 总价（瓜子）= price * giftNum
+    #[serde(rename = "totalCoin")]
+    pub total_coin: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuperChatEvent {
+    #[serde(flatten)]
+    pub common: EventCommon,
+    // 醒目留言 id（用于删除/撤回时匹配）
+    #[serde(rename = "scId", default, skip_serializing_if = "Option::is_none")]
+    pub sc_id: Option<u64>,
+    // 醒目留言原文（text 为带前缀的展示文本）
+    pub message: String,
+    // 价格（元）
+    pub price: u64,
+    // 留言持续时间（秒）
+    pub duration: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuardBuyEvent {
+    #[serde(flatten)]
+    pub common: EventCommon,
+    // 舰队名称（舰长/提督/总督），购买的等级见 guardLevel
+    #[serde(rename = "giftName")]
+    pub gift_name: String,
+    // 购买数量（月）
+    pub num: u64,
+    // 单价（金瓜子）
+    pub price: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InteractEvent {
+    #[serde(flatten)]
+    pub common: EventCommon,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiveStatusEvent {
+    #[serde(flatten)]
+    pub common: EventCommon,
+    pub title: String,
+    #[serde(rename = "areaName")]
+    pub area_name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuperChatDeleteEvent {
+    #[serde(flatten)]
+    pub common: EventCommon,
+    #[serde(rename = "scIds")]
+    pub sc_ids: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchedEvent {
+    #[serde(flatten)]
+    pub common: EventCommon,
+    // 看过人数
+    pub num: u64,
+}
+
+pub fn new_event_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// 醒目留言的事件 id 由连接与 scId 决定，上游删除时无需缓存即可算出要撤回的 id
+pub fn super_chat_event_id(connection_id: &str, sc_id: u64) -> String {
+    format!("{connection_id}:sc:{sc_id}")
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+fn hms_from_unix_ms(ms: u64) -> String {
+    use chrono::TimeZone;
+
+    chrono::Local
+        .timestamp_millis_opt(ms as i64)
+        .single()
+        .map(|dt| dt.format("%H:%M:%S").to_string())
+        .unwrap_or_else(|| chrono::Local::now().format("%H:%M:%S").to_string())
+}
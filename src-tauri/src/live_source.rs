@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use crate::bili_websocket_client::Dispatched;
+use crate::dedup::Deduper;
+use crate::live_event::LiveEvent;
+use crate::sse_server::DroppedStats;
+
+// 平台 -> 管线的事件队列长度；管线跟不上时 emit 等待，而不是把整段录制堆进内存
+const EVENT_QUEUE_CAPACITY: usize = 1024;
+
+/// 直播平台接入点：每个平台（B 站公屏 / 开放平台 / 其它平台）实现它。
+/// 平台只负责连接与解析，解析出的 LiveEvent 经 SourceContext 进入统一的过滤 -> 样式 -> SSE 管线。
+pub trait LiveSource: Send + Sync + 'static {
+    /// 平台标识，用于日志与连接列表展示
+    fn platform(&self) -> &'static str;
+
+    /// 连接并持续运行（含重连），直到放弃重连才返回；断开时任务会被直接 abort
+    fn connect(self: Arc<Self>, ctx: SourceContext) -> BoxFuture<'static, ()>;
+
+    /// 断开后的平台侧清理（例如开放平台 app/end），默认无需清理
+    fn disconnect(self: Arc<Self>, _connection_id: String) -> BoxFuture<'static, ()> {
+        Box::pin(async {})
+    }
+}
+
+/// 平台与管线之间的通道：事件流 + 状态上报
+#[derive(Clone)]
+pub struct SourceContext {
+    connection_id: String,
+    events: mpsc::Sender<LiveEvent>,
+}
+
+impl SourceContext {
+    pub(crate) fn new(connection_id: String) -> (Self, mpsc::Receiver<LiveEvent>) {
+        let (events, rx) = mpsc::channel(EVENT_QUEUE_CAPACITY);
+        (Self { connection_id, events }, rx)
+    }
+
+    pub fn connection_id(&self) -> &str {
+        &self.connection_id
+    }
+
+    /// 推送一条解析好的事件，队列满时等待管线消费；管线已退出（连接被断开）时静默丢弃
+    pub async fn emit(&self, event: LiveEvent) {
+        let _ = self.events.send(event).await;
+    }
+
+    /// 上报连接状态（websocket-status 事件），code 供前端分支
+    pub async fn status(&self, status: &str, code: Option<&str>, message: &str) {
+        crate::bili_websocket_client::emit_status_with_code(&self.connection_id, status, code, message).await;
+    }
+
+    /// 上报真实房间号与主播信息，用于来源标记与“主播/本房间粉丝牌”判定
+    pub async fn set_room(&self, room_id: Option<i64>, owner_uid: Option<i64>, owner_face_url: Option<String>) {
+        crate::bili_websocket_client::set_connection_room(&self.connection_id, room_id, owner_uid, owner_face_url).await;
+    }
+
+    /// 连接是否已被要求停止（重连前检查）
+    pub async fn should_stop(&self) -> bool {
+        crate::bili_websocket_client::should_stop(&self.connection_id).await
+    }
+}
+
+/// 管线统计：事件总数、转发数与按原因的丢弃数（回放结束时输出，供回归对比）
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineStats {
+    pub events: u64,
+    pub forwarded: u64,
+    pub dropped: DroppedStats,
+}
+
+/// 消费某个连接的事件流：过滤后交给样式与 SSE 推送，平台实现不需要关心这部分。
+/// replay 为 true 时去重按事件时间戳、使用本连接独立的窗口，结果与回放倍速无关；
+/// 回放事件也不会再写入历史记录等持久统计
+pub(crate) async fn run_pipeline(
+    connection_id: String,
+    mut events: mpsc::Receiver<LiveEvent>,
+    replay: bool,
+) -> PipelineStats {
+    let mut deduper = replay.then(Deduper::default);
+    let mut stats = PipelineStats::default();
+    while let Some(event) = events.recv().await {
+        stats.events += 1;
+        match crate::bili_websocket_client::dispatch_event(&connection_id, event, deduper.as_mut()).await {
+            Dispatched::Forwarded => stats.forwarded += 1,
+            Dispatched::Dropped(reason) => stats.dropped.add(reason),
+            Dispatched::Ignored => {}
+        }
+    }
+    stats
+}
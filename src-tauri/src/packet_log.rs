@@ -0,0 +1,83 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+/// 原始包录制文件格式（*.blpk）：
+///   文件头 b"BLPK" + 版本号 u8
+///   之后每条记录：接收时刻 Unix 毫秒 u64 BE + 帧长度 u32 BE + 帧原始字节（即 handle_packet 收到的 WebSocket 二进制帧）
+pub const PACKET_LOG_MAGIC: &[u8; 4] = b"BLPK";
+pub const PACKET_LOG_VERSION: u8 = 1;
+// 单帧上限，防止读到损坏文件时按错误的长度分配内存
+const MAX_FRAME_LEN: u32 = 16 * 1024 * 1024;
+
+pub struct PacketLogWriter {
+    writer: BufWriter<File>,
+    frames: u64,
+}
+
+impl PacketLogWriter {
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(PACKET_LOG_MAGIC)?;
+        writer.write_all(&[PACKET_LOG_VERSION])?;
+        writer.flush()?;
+        Ok(Self { writer, frames: 0 })
+    }
+
+    /// 写入一帧并立即 flush：程序崩溃时已收到的帧也能保留下来用于复现
+    pub fn write_frame(&mut self, recv_ms: u64, frame: &[u8]) -> std::io::Result<()> {
+        let len = u32::try_from(frame.len()).map_err(|_| std::io::Error::other("帧过大"))?;
+        self.writer.write_all(&recv_ms.to_be_bytes())?;
+        self.writer.write_all(&len.to_be_bytes())?;
+        self.writer.write_all(frame)?;
+        self.writer.flush()?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+}
+
+pub struct PacketLogReader {
+    reader: BufReader<File>,
+}
+
+impl PacketLogReader {
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut header = [0u8; 5];
+        reader.read_exact(&mut header)?;
+        if &header[0..4] != PACKET_LOG_MAGIC || header[4] != PACKET_LOG_VERSION {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "不是有效的录制文件"));
+        }
+        Ok(Self { reader })
+    }
+
+    /// 读取下一帧 (recv_ms, frame)；正常读到文件末尾返回 Ok(None)，末尾被截断的记录同样视为结束
+    pub fn next_frame(&mut self) -> std::io::Result<Option<(u64, Vec<u8>)>> {
+        let mut head = [0u8; 12];
+        match self.reader.read_exact(&mut head) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let recv_ms = u64::from_be_bytes(head[0..8].try_into().unwrap());
+        let len = u32::from_be_bytes(head[8..12].try_into().unwrap());
+        if len > MAX_FRAME_LEN {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("帧长度异常: {len}")));
+        }
+        let mut frame = vec![0u8; len as usize];
+        match self.reader.read_exact(&mut frame) {
+            Ok(()) => Ok(Some((recv_ms, frame))),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use futures::future::BoxFuture;
+
+use crate::error::AppError;
+use crate::live_event::LiveEvent;
+use crate::live_source::{LiveSource, SourceContext};
+use crate::packet_log::{PacketLogReader, PACKET_LOG_MAGIC};
+
+// 录制里的长时间空白（断流、下播）按原速回放没有意义，单次等待封顶
+const MAX_REPLAY_GAP: Duration = Duration::from_secs(30);
+// 倍速范围：太小会一次睡上几小时，太大与不等待没有区别
+pub const MIN_REPLAY_SPEED: f64 = 0.1;
+pub const MAX_REPLAY_SPEED: f64 = 100.0;
+
+/// 倍速只接受 0（不等待）或 [MIN_REPLAY_SPEED, MAX_REPLAY_SPEED]；
+/// NaN / 极小值会让 Duration::div_f64 panic 或睡眠过长
+pub fn validate_replay_speed(speed: f64) -> Result<(), AppError> {
+    if speed == 0.0 || (MIN_REPLAY_SPEED..=MAX_REPLAY_SPEED).contains(&speed) {
+        Ok(())
+    } else {
+        Err(AppError::InvalidArgument(format!(
+            "回放倍速无效: {speed}（0 表示不等待，或 {MIN_REPLAY_SPEED}~{MAX_REPLAY_SPEED}）"
+        )))
+    }
+}
+
+/// 回放录制文件，事件走与直播完全相同的 过滤 -> 样式 -> SSE 管线：
+/// - packet_log（*.blpk）：原始帧逐个交给 handle_packet 重新解析
+/// - 事件日志（JSON Lines，每行一个 LiveEvent，如导出的 transcript）：直接进入管线
+///
+/// speed 为倍速（2.0 = 两倍速）；0 表示不等待、尽快回放（用于确定性回归测试），见 validate_replay_speed
+pub struct ReplaySource {
+    path: PathBuf,
+    speed: f64,
+}
+
+impl ReplaySource {
+    pub fn new(path: impl Into<PathBuf>, speed: f64) -> Self {
+        Self {
+            path: path.into(),
+            speed,
+        }
+    }
+
+    /// 按原始间隔（除以倍速）等待；时间倒退（多连接混录）时不等待
+    async fn wait_gap(&self, prev_ms: Option<u64>, cur_ms: u64) {
+        if self.speed == 0.0 {
+            return;
+        }
+        let Some(prev_ms) = prev_ms else {
+            return;
+        };
+        let gap = Duration::from_millis(cur_ms.saturating_sub(prev_ms)).min(MAX_REPLAY_GAP);
+        if !gap.is_zero() {
+            tokio::time::sleep(gap.div_f64(self.speed)).await;
+        }
+    }
+
+    async fn replay_packets(&self, ctx: &SourceContext) -> std::io::Result<u64> {
+        let mut reader = PacketLogReader::open(&self.path)?;
+        let mut prev_ms = None;
+        let mut count = 0;
+        while let Some((recv_ms, frame)) = reader.next_frame()? {
+            if ctx.should_stop().await {
+                break;
+            }
+            self.wait_gap(prev_ms, recv_ms).await;
+            prev_ms = Some(recv_ms);
+            // 录制里的鉴权回包失败只说明当时的连接状态，回放时忽略
+            let _ = crate::bili_websocket_client::handle_packet(ctx, &frame).await;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    async fn replay_events(&self, ctx: &SourceContext) -> std::io::Result<u64> {
+        let reader = BufReader::new(File::open(&self.path)?);
+        let mut prev_ms = None;
+        let mut count = 0;
+        for (line_no, line) in reader.lines().enumerate() {
+            if ctx.should_stop().await {
+                break;
+            }
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event = match serde_json::from_str::<LiveEvent>(&line) {
+                Ok(event) => event,
+                Err(e) => {
+                    // 样式配置、retract 等非事件行直接跳过
+                    if crate::bili_websocket_client::ws_debug_enabled() {
+                        eprintln!("[Replay][DEBUG] 跳过第 {} 行: {e}", line_no + 1);
+                    }
+                    continue;
+                }
+            };
+            let ts = event.common().timestamp;
+            self.wait_gap(prev_ms, ts).await;
+            prev_ms = Some(ts);
+            ctx.emit(event).await;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn is_packet_log(&self) -> std::io::Result<bool> {
+        let mut magic = [0u8; 4];
+        let n = File::open(&self.path)?.read(&mut magic)?;
+        Ok(n == magic.len() && &magic == PACKET_LOG_MAGIC)
+    }
+}
+
+impl LiveSource for ReplaySource {
+    fn platform(&self) -> &'static str {
+        "replay"
+    }
+
+    fn connect(self: Arc<Self>, ctx: SourceContext) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            let connection_id = ctx.connection_id().to_string();
+            let path = self.path.display().to_string();
+
+            let result = match self.is_packet_log() {
+                Ok(true) => {
+                    ctx.status("connected", None, "回放中（原始包）").await;
+                    self.replay_packets(&ctx).await
+                }
+                Ok(false) => {
+                    ctx.status("connected", None, "回放中（事件日志）").await;
+                    self.replay_events(&ctx).await
+                }
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Ok(count) => {
+                    println!("[Replay][{connection_id}] 回放结束: {path}（{count} 条）");
+                    ctx.status("disconnected", None, &format!("回放结束，共 {} 条", count)).await;
+                }
+                Err(e) => {
+                    eprintln!("[Replay][{connection_id}] 回放失败 {path}: {e}");
+                    ctx.status("error", Some("IO"), &format!("回放失败: {}", e)).await;
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bili_websocket_client::{self, RoomKeyType};
+
+    #[tokio::test]
+    async fn replays_event_log_through_pipeline() {
+        // 不改全局过滤配置（默认全部放行），也不影响其它连接；去重等过滤结果见 filter_test
+        let connection_id = format!("replay-test-{}", crate::live_event::new_event_id());
+        let path = std::env::temp_dir().join(format!("{connection_id}.jsonl"));
+        std::fs::write(&path, include_str!("../tests/fixtures/replay_events.jsonl")).unwrap();
+
+        let source = Arc::new(ReplaySource::new(path.clone(), 0.0));
+        let path_str = path.display().to_string();
+        bili_websocket_client::spawn_connection(connection_id.clone(), path_str, RoomKeyType::Replay, source)
+            .await
+            .unwrap();
+        let stats = bili_websocket_client::wait_pipeline(&connection_id).await.unwrap();
+        let _ = bili_websocket_client::disconnect_websocket(&connection_id).await;
+        let _ = std::fs::remove_file(&path);
+
+        // 样式配置行跳过，其余事件全部排空后才返回统计
+        assert_eq!(stats.events, 5);
+        assert_eq!(stats.forwarded, 5);
+        assert_eq!(stats.dropped.total, 0);
+    }
+
+    #[test]
+    fn rejects_invalid_speed() {
+        assert!(validate_replay_speed(0.0).is_ok());
+        assert!(validate_replay_speed(1.0).is_ok());
+        assert!(validate_replay_speed(f64::NAN).is_err());
+        assert!(validate_replay_speed(1e-300).is_err());
+        assert!(validate_replay_speed(-1.0).is_err());
+        assert!(validate_replay_speed(1000.0).is_err());
+    }
+}
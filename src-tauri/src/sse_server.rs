@@ -5,7 +5,6 @@ use axum::{
     routing::{get, post},
     Json, Router,
 };
-use axum::http::StatusCode;
 use futures::Stream;
 use tower_http::services::ServeDir;
 use tower_http::cors::CorsLayer;
@@ -16,6 +15,8 @@ use tokio::sync::{broadcast, RwLock};
 use uuid::Uuid;
 use async_stream;
 
+use crate::error::AppError;
+
 // 直接内置预览页，避免依赖运行时工作目录下的静态文件路径
 static PREVIEW_HTML: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/../src/public/preview.html"));
 
@@ -46,7 +47,7 @@ pub struct AuthConfig {
     pub token: Option<String>,
 }
 
-fn check_token(auth: &AuthConfig, query: &HashMap<String, String>) -> Result<(), (StatusCode, String)> {
+fn check_token(auth: &AuthConfig, query: &HashMap<String, String>) -> Result<(), AppError> {
     let Some(expected) = auth.token.as_deref() else {
         return Ok(());
     };
@@ -54,7 +55,7 @@ fn check_token(auth: &AuthConfig, query: &HashMap<String, String>) -> Result<(),
     if provided == expected {
         Ok(())
     } else {
-        Err((StatusCode::UNAUTHORIZED, "Unauthorized".to_string()))
+        Err(AppError::Unauthorized)
     }
 }
 
@@ -63,6 +64,41 @@ pub struct Stats {
     pub connections: usize,
     pub danmu_count: u64,
     pub last_activity: Option<u64>,
+    // 被过滤/去重丢弃的弹幕
+    pub dropped: DroppedStats,
+}
+
+/// 按原因统计被丢弃的弹幕数
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DroppedStats {
+    pub total: u64,
+    pub filtered: u64,
+    pub muted: u64,
+    pub blacklisted: u64,
+    pub duplicate: u64,
+    pub user_repeat: u64,
+    pub similar: u64,
+    pub rate_limited: u64,
+    pub rule: u64,
+}
+
+impl DroppedStats {
+    pub fn add(&mut self, reason: crate::bili_websocket_client::DropReason) {
+        use crate::bili_websocket_client::DropReason;
+        self.total += 1;
+        let counter = match reason {
+            DropReason::Filtered => &mut self.filtered,
+            DropReason::Muted => &mut self.muted,
+            DropReason::Blacklisted => &mut self.blacklisted,
+            DropReason::Duplicate => &mut self.duplicate,
+            DropReason::UserRepeat => &mut self.user_repeat,
+            DropReason::Similar => &mut self.similar,
+            DropReason::RateLimited => &mut self.rate_limited,
+            DropReason::Rule => &mut self.rule,
+        };
+        *counter += 1;
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -158,6 +194,9 @@ async fn build_config_message(state: &Arc<AppState>) -> serde_json::Value {
 // 弹幕数据结构
 #[derive(Serialize, Deserialize, Clone)]
 pub struct DanmuData {
+    // 事件 id：撤回（/api/retract）时按此匹配；调用方不传则自动生成
+    #[serde(default = "crate::live_event::new_event_id")]
+    pub id: String,
     #[serde(rename = "type")]
     pub danmu_type: String,
     pub text: String,
@@ -278,8 +317,8 @@ pub async fn sse_handler(
     // 可选 token 鉴权
     {
         let auth = state.auth.read().await.clone();
-        if let Err((code, msg)) = check_token(&auth, &query) {
-            return (code, msg).into_response();
+        if let Err(e) = check_token(&auth, &query) {
+            return e.into_response();
         }
     }
 
@@ -358,7 +397,7 @@ pub async fn send_danmu_handler(
     State(state): State<Arc<AppState>>,
     Query(query): Query<HashMap<String, String>>,
     Json(mut danmu_data): Json<DanmuData>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
+) -> Result<Json<serde_json::Value>, AppError> {
     // 可选 token 鉴权
     {
         let auth = state.auth.read().await.clone();
@@ -367,7 +406,7 @@ pub async fn send_danmu_handler(
 
     // 验证必要字段
     if danmu_data.text.is_empty() {
-        return Err((axum::http::StatusCode::BAD_REQUEST, "缺少text字段".to_string()));
+        return Err(AppError::InvalidArgument("缺少text字段".to_string()));
     }
     
     // 设置时间戳
@@ -400,27 +439,28 @@ pub async fn send_danmu_handler(
 pub async fn status_handler(
     State(state): State<Arc<AppState>>,
     Query(query): Query<HashMap<String, String>>,
-) -> Json<serde_json::Value> {
-    // 可选 token 鉴权（状态接口失败时直接返回空状态，避免把 handler 改成 Result）
+) -> Result<Json<serde_json::Value>, AppError> {
+    // 可选 token 鉴权
     {
         let auth = state.auth.read().await.clone();
-        if check_token(&auth, &query).is_err() {
-            return Json(serde_json::json!({"error": "unauthorized"}));
-        }
+        check_token(&auth, &query)?;
     }
 
     let connections = state.sse_connections.read().await.len();
     let stats = state.stats.read().await.clone();
     
-    Json(serde_json::json!({
+    Ok(Json(serde_json::json!({
         "connections": connections,
         "danmu_count": stats.danmu_count,
         "last_activity": stats.last_activity,
+        "dropped": stats.dropped,
+        "metrics": crate::bili_websocket_client::list_metrics().await,
+        "analytics": crate::analytics::snapshot().await,
         "timestamp": std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
             .as_millis() as u64
-    }))
+    })))
 }
 
 // 更新配置端点
@@ -428,7 +468,7 @@ pub async fn update_config_handler(
     State(state): State<Arc<AppState>>,
     Query(query): Query<HashMap<String, String>>,
     Json(config_data): Json<serde_json::Value>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
+) -> Result<Json<serde_json::Value>, AppError> {
     // 可选 token 鉴权
     {
         let auth = state.auth.read().await.clone();
@@ -448,11 +488,218 @@ pub async fn update_config_handler(
                 "message": "配置更新成功"
             })));
         } else {
-            return Err((axum::http::StatusCode::BAD_REQUEST, "配置格式错误".to_string()));
+            return Err(AppError::InvalidArgument("配置格式错误".to_string()));
         }
     }
     
-    Err((axum::http::StatusCode::BAD_REQUEST, "缺少config字段".to_string()))
+    Err(AppError::InvalidArgument("缺少config字段".to_string()))
+}
+
+// 禁言请求：minutes 分钟内不再转发该 uid 的弹幕
+#[derive(Deserialize)]
+pub struct MuteRequest {
+    pub uid: u64,
+    pub minutes: u64,
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+// 撤回请求：ids 为要撤回的事件 id
+#[derive(Deserialize)]
+pub struct RetractRequest {
+    pub ids: Vec<String>,
+}
+
+// 历史记录查询：/api/history?q=&user=&since=&type=&roomId=&limit=
+pub async fn history_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    // 可选 token 鉴权
+    {
+        let auth = state.auth.read().await.clone();
+        check_token(&auth, &query)?;
+    }
+
+    let history_query = crate::history::HistoryQuery::from_params(&query)?;
+    let entries = crate::history::query_history(&history_query).await?;
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "count": entries.len(),
+        "entries": entries,
+    })))
+}
+
+// 本场排行榜：/api/leaderboard?limit=10
+pub async fn leaderboard_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    // 可选 token 鉴权
+    {
+        let auth = state.auth.read().await.clone();
+        check_token(&auth, &query)?;
+    }
+
+    let limit = match query.get("limit").map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        Some(s) => s
+            .parse::<usize>()
+            .map_err(|_| AppError::InvalidArgument("limit 必须是数字".to_string()))?,
+        None => crate::leaderboard::LEADERBOARD_DEFAULT_LIMIT,
+    };
+    let snapshot = crate::leaderboard::snapshot(limit).await;
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "leaderboard": snapshot,
+    })))
+}
+
+// 滚动窗口弹幕分析（每分钟弹幕数、独立发言人数、高频词、刷屏判定）：/api/analytics
+pub async fn analytics_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    // 可选 token 鉴权
+    {
+        let auth = state.auth.read().await.clone();
+        check_token(&auth, &query)?;
+    }
+
+    let snapshot = crate::analytics::snapshot().await;
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "analytics": snapshot,
+    })))
+}
+
+// 当前禁言列表：GET /api/mute
+pub async fn mute_list_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    // 可选 token 鉴权
+    {
+        let auth = state.auth.read().await.clone();
+        check_token(&auth, &query)?;
+    }
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "muted": crate::bili_websocket_client::list_muted_users().await,
+    })))
+}
+
+// 临时禁言（只影响字幕层，不在直播间禁言）：POST /api/mute {"uid":..,"minutes":..}
+pub async fn mute_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+    Json(req): Json<MuteRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    // 可选 token 鉴权
+    {
+        let auth = state.auth.read().await.clone();
+        check_token(&auth, &query)?;
+    }
+
+    let muted = crate::bili_websocket_client::mute_user(req.uid, req.minutes, req.user, req.reason).await?;
+    crate::persist_muted_users().await?;
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "message": format!("已禁言 {} {} 分钟", req.uid, req.minutes),
+        "muted": muted,
+    })))
+}
+
+// 解除禁言：DELETE /api/mute?uid=
+pub async fn unmute_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    // 可选 token 鉴权
+    {
+        let auth = state.auth.read().await.clone();
+        check_token(&auth, &query)?;
+    }
+
+    let uid = query
+        .get("uid")
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .ok_or_else(|| AppError::InvalidArgument("缺少uid参数".to_string()))?;
+    if !crate::bili_websocket_client::unmute_user(uid).await {
+        return Err(AppError::InvalidArgument(format!("uid {} 未被禁言", uid)));
+    }
+    crate::persist_muted_users().await?;
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "message": format!("已解除禁言: {}", uid),
+    })))
+}
+
+// 过滤 dry-run：POST /api/filter/test {"config":{..},"event":{"type":"danmu","text":".."}} 或 {"events":[..]}
+pub async fn filter_test_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+    Json(req): Json<crate::filter_test::FilterTestRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    // 可选 token 鉴权
+    {
+        let auth = state.auth.read().await.clone();
+        check_token(&auth, &query)?;
+    }
+
+    // 读取本地文件只开放给桌面端（test_danmu_filter 命令）
+    if req.path.is_some() {
+        return Err(AppError::InvalidArgument("HTTP 接口不支持 path，请直接提交 events".to_string()));
+    }
+    let report = crate::filter_test::run(req).await?;
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "report": report,
+    })))
+}
+
+// 撤回字幕端点（房管手动撤回不当内容）
+pub async fn retract_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+    Json(req): Json<RetractRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    // 可选 token 鉴权
+    {
+        let auth = state.auth.read().await.clone();
+        check_token(&auth, &query)?;
+    }
+
+    let ids: Vec<String> = req.ids.into_iter().filter(|id| !id.is_empty()).collect();
+    if ids.is_empty() {
+        return Err(AppError::InvalidArgument("缺少ids字段".to_string()));
+    }
+
+    let count = ids.len();
+    broadcast_retract(&state, ids).await;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "message": format!("已撤回 {} 条", count)
+    })))
+}
+
+// 内部函数：广播撤回消息，预览端移除队列中与屏幕上对应 id 的字幕
+pub async fn broadcast_retract(state: &Arc<AppState>, ids: Vec<String>) {
+    send_to_all_connections(
+        state,
+        serde_json::json!({
+            "type": "retract",
+            "ids": ids,
+            "time": std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64
+        }),
+    )
+    .await;
 }
 
 // 内部函数：发送消息到所有连接
@@ -462,6 +709,9 @@ pub async fn send_to_all_connections(state: &Arc<AppState>, msg: serde_json::Val
     if std::env::var("YJDANMU_SSE_DEBUG").is_ok() {
         println!("[弹幕] {}", serde_json::to_string(&msg).unwrap_or_default());
     }
+    // 场次记录（未开启时直接返回）
+    crate::transcript::append(&msg).await;
+
     let connections = state.sse_connections.read().await;
     let connection_ids: Vec<String> = connections.keys().cloned().collect();
     
@@ -480,6 +730,12 @@ pub fn create_app(state: Arc<AppState>) -> Router {
         .route("/api/send-danmu", post(send_danmu_handler))
         .route("/api/status", get(status_handler))
         .route("/api/config", post(update_config_handler))
+        .route("/api/retract", post(retract_handler))
+        .route("/api/history", get(history_handler))
+        .route("/api/leaderboard", get(leaderboard_handler))
+        .route("/api/analytics", get(analytics_handler))
+        .route("/api/mute", get(mute_list_handler).post(mute_handler).delete(unmute_handler))
+        .route("/api/filter/test", post(filter_test_handler))
         .fallback_service(ServeDir::new("../frontend/public"))
         .layer(
             CorsLayer::new()
@@ -0,0 +1,602 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc},
+    time::Duration,
+};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{oneshot, RwLock};
+
+use crate::error::AppError;
+use crate::sse_server::Config;
+use crate::StyleProfile;
+
+/// 场次记录：开启后，每条经 send_to_all_connections 推送的事件（及 retract）按行追加到
+/// 配置目录 transcripts/session-*.jsonl，一次运行（或重新开启记录）为一个场次。
+/// 导出时据此生成 JSONL / CSV / SRT / ASS。
+/// 文件由每个场次独立的写线程写入（见 run_writer），推送路径上只是往通道里塞一行。
+#[derive(Default)]
+struct TranscriptState {
+    enabled: bool,
+    dir: Option<PathBuf>,
+    // 当前场次：收到第一条事件时才创建文件，避免产生空文件
+    current: Option<Session>,
+}
+
+struct Session {
+    path: PathBuf,
+    // 丢弃发送端即结束场次：写线程落盘后退出
+    writer: mpsc::Sender<WriterCommand>,
+}
+
+enum WriterCommand {
+    Line(String),
+    // 导出前落盘，完成后回复
+    Flush(oneshot::Sender<()>),
+}
+
+// 写线程空闲这么久就把缓冲落盘
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+static TRANSCRIPT: Lazy<Arc<RwLock<TranscriptState>>> = Lazy::new(|| Arc::new(RwLock::new(TranscriptState::default())));
+
+// 会写入场次记录的消息类型（与 LiveEvent::kind 一致，另加 retract 用于导出时剔除被撤回的事件）
+const RECORDED_TYPES: &[&str] = &[
+    "danmu",
+    "gift",
+    "superChat",
+    "guardBuy",
+    "enter",
+    "follow",
+    "share",
+    "like",
+    "watched",
+    "liveStart",
+    "liveEnd",
+    "retract",
+];
+
+pub async fn set_transcript_config(dir: Option<PathBuf>, enabled: bool) {
+    let mut st = TRANSCRIPT.write().await;
+    // 关闭或目录变化时结束当前场次，下次开启另起一个文件
+    if !enabled || st.dir != dir {
+        st.current = None;
+    }
+    st.enabled = enabled;
+    st.dir = dir;
+}
+
+pub fn transcripts_dir(config_dir: &Path) -> PathBuf {
+    config_dir.join("transcripts")
+}
+
+/// 当前场次文件路径（尚未收到事件时为 None）
+pub async fn current_session() -> Option<PathBuf> {
+    TRANSCRIPT.read().await.current.as_ref().map(|s| s.path.clone())
+}
+
+fn start_session(dir: &Path) -> std::io::Result<Session> {
+    let path = dir.join(format!("session-{}.jsonl", chrono::Local::now().format("%Y%m%d-%H%M%S")));
+    fs::create_dir_all(dir)?;
+    let file = File::options().create(true).append(true).open(&path)?;
+    let (tx, rx) = mpsc::channel();
+    let thread_path = path.clone();
+    std::thread::Builder::new()
+        .name("transcript-writer".to_string())
+        .spawn(move || run_writer(thread_path, BufWriter::new(file), rx))?;
+    Ok(Session { path, writer: tx })
+}
+
+/// 写线程：逐行写入缓冲，空闲 FLUSH_INTERVAL 或场次结束时落盘
+fn run_writer(path: PathBuf, mut writer: BufWriter<File>, rx: mpsc::Receiver<WriterCommand>) {
+    let flush = |writer: &mut BufWriter<File>| {
+        if let Err(e) = writer.flush() {
+            eprintln!("[Transcript] 写入失败 {}: {e}", path.display());
+        }
+    };
+    let mut dirty = false;
+    loop {
+        match rx.recv_timeout(FLUSH_INTERVAL) {
+            Ok(WriterCommand::Line(line)) => {
+                if let Err(e) = writeln!(writer, "{line}") {
+                    eprintln!("[Transcript] 写入失败 {}: {e}", path.display());
+                }
+                dirty = true;
+            }
+            Ok(WriterCommand::Flush(done)) => {
+                flush(&mut writer);
+                dirty = false;
+                let _ = done.send(());
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                if dirty {
+                    flush(&mut writer);
+                    dirty = false;
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    flush(&mut writer);
+}
+
+pub async fn append(msg: &serde_json::Value) {
+    let Some(kind) = msg.get("type").and_then(|v| v.as_str()) else {
+        return;
+    };
+    if !RECORDED_TYPES.contains(&kind) {
+        return;
+    }
+
+    let mut st = TRANSCRIPT.write().await;
+    if !st.enabled {
+        return;
+    }
+    if st.current.is_none() {
+        let Some(dir) = st.dir.clone() else {
+            return;
+        };
+        match start_session(&dir) {
+            Ok(session) => {
+                println!("[Transcript] 新场次: {}", session.path.display());
+                st.current = Some(session);
+            }
+            Err(e) => {
+                eprintln!("[Transcript] 创建场次文件失败，关闭记录: {e}");
+                st.enabled = false;
+                return;
+            }
+        }
+    }
+
+    let Some(session) = st.current.as_ref() else {
+        return;
+    };
+    let line = serde_json::to_string(msg).unwrap_or_default();
+    if session.writer.send(WriterCommand::Line(line)).is_err() {
+        eprintln!("[Transcript] 写线程已退出，关闭记录: {}", session.path.display());
+        st.current = None;
+        st.enabled = false;
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscriptInfo {
+    pub path: String,
+    pub name: String,
+    pub size: u64,
+    pub current: bool,
+}
+
+/// 列出场次记录，最新的在前
+pub async fn list_transcripts(config_dir: &Path) -> Vec<TranscriptInfo> {
+    let current = current_session().await;
+    let Ok(entries) = fs::read_dir(transcripts_dir(config_dir)) else {
+        return Vec::new();
+    };
+    let mut list = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "jsonl"))
+        .map(|p| TranscriptInfo {
+            size: fs::metadata(&p).map(|m| m.len()).unwrap_or(0),
+            name: p.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default(),
+            current: current.as_ref() == Some(&p),
+            path: p.to_string_lossy().to_string(),
+        })
+        .collect::<Vec<_>>();
+    list.sort_by(|a, b| b.name.cmp(&a.name));
+    list
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptFormat {
+    Jsonl,
+    Csv,
+    Srt,
+    Ass,
+}
+
+impl TranscriptFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            TranscriptFormat::Jsonl => "export.jsonl",
+            TranscriptFormat::Csv => "csv",
+            TranscriptFormat::Srt => "srt",
+            TranscriptFormat::Ass => "ass",
+        }
+    }
+}
+
+/// 导出场次记录。start_ms 为直播开始时刻（Unix 毫秒），字幕时间轴以它为 0 点；
+/// 不传时取记录里第一条 liveStart，没有则取第一条事件的时间。
+pub async fn export_transcript(
+    session: &Path,
+    format: TranscriptFormat,
+    out_path: Option<PathBuf>,
+    start_ms: Option<u64>,
+    profile: &StyleProfile,
+) -> Result<PathBuf, AppError> {
+    // 正在写的场次先落盘
+    let flushed = TRANSCRIPT
+        .read()
+        .await
+        .current
+        .as_ref()
+        .filter(|s| s.path == session)
+        .map(|s| {
+            let (done, flushed) = oneshot::channel();
+            let _ = s.writer.send(WriterCommand::Flush(done));
+            flushed
+        });
+    if let Some(flushed) = flushed {
+        let _ = flushed.await;
+    }
+
+    let mut events = read_session(session)?;
+    // 多个连接混在一个场次里时写入顺序不一定是时间顺序
+    events.sort_by_key(event_time_ms);
+    let start_ms = start_ms
+        .or_else(|| {
+            events
+                .iter()
+                .find(|e| event_kind(e) == "liveStart")
+                .map(event_time_ms)
+        })
+        .or_else(|| events.first().map(event_time_ms))
+        .unwrap_or(0);
+
+    let out_path = out_path.unwrap_or_else(|| session.with_extension(format.extension()));
+    let body = match format {
+        TranscriptFormat::Jsonl => events
+            .iter()
+            .map(|e| serde_json::to_string(e).unwrap_or_default() + "\n")
+            .collect::<String>(),
+        TranscriptFormat::Csv => to_csv(&events, start_ms),
+        TranscriptFormat::Srt => to_srt(&events, start_ms),
+        TranscriptFormat::Ass => to_ass(&events, start_ms, profile),
+    };
+    if let Some(parent) = out_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| AppError::Io(format!("创建导出目录失败: {e}")))?;
+    }
+    // CSV 加 BOM，Excel 打开中文不乱码
+    let bytes = if format == TranscriptFormat::Csv {
+        [b"\xEF\xBB\xBF".as_slice(), body.as_bytes()].concat()
+    } else {
+        body.into_bytes()
+    };
+    fs::write(&out_path, bytes).map_err(|e| AppError::Io(format!("写入导出文件失败: {e}")))?;
+    Ok(out_path)
+}
+
+/// 读取场次记录：剔除被 retract 撤回的事件，只保留事件行
+fn read_session(session: &Path) -> Result<Vec<serde_json::Value>, AppError> {
+    let file = File::open(session).map_err(|e| AppError::Io(format!("打开场次记录失败: {e}")))?;
+    let mut events = Vec::new();
+    let mut retracted = HashSet::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| AppError::Io(format!("读取场次记录失败: {e}")))?;
+        let Ok(msg) = serde_json::from_str::<serde_json::Value>(&line) else {
+            continue;
+        };
+        if event_kind(&msg) == "retract" {
+            if let Some(ids) = msg.get("ids").and_then(|v| v.as_array()) {
+                retracted.extend(ids.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()));
+            }
+            continue;
+        }
+        events.push(msg);
+    }
+    events.retain(|e| !e.get("id").and_then(|v| v.as_str()).is_some_and(|id| retracted.contains(id)));
+    Ok(events)
+}
+
+fn event_kind(e: &serde_json::Value) -> &str {
+    e.get("type").and_then(|v| v.as_str()).unwrap_or("")
+}
+
+/// 事件时刻：优先 timestamp（Unix 毫秒），手动发送的弹幕 timestamp 是 "HH:MM:SS" 字符串，回退到 time
+fn event_time_ms(e: &serde_json::Value) -> u64 {
+    e.get("timestamp")
+        .and_then(|v| v.as_u64())
+        .or_else(|| e.get("time").and_then(|v| v.as_u64()))
+        .unwrap_or(0)
+}
+
+fn str_field<'a>(e: &'a serde_json::Value, key: &str) -> &'a str {
+    e.get(key).and_then(|v| v.as_str()).unwrap_or("")
+}
+
+/// 字幕显示时长：与预览一致，displayDuration + fadeDuration
+fn event_duration_ms(e: &serde_json::Value) -> u64 {
+    let display = e.get("displayDuration").and_then(|v| v.as_u64()).unwrap_or(3000);
+    let fade = e.get("fadeDuration").and_then(|v| v.as_u64()).unwrap_or(1000);
+    display + fade
+}
+
+/// 字幕里显示的文本：带上用户名
+fn caption_text(e: &serde_json::Value) -> String {
+    let user = str_field(e, "user");
+    let text = str_field(e, "text");
+    if user.is_empty() {
+        text.to_string()
+    } else {
+        format!("{user}: {text}")
+    }
+}
+
+fn csv_escape(v: &str) -> String {
+    if v.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", v.replace('"', "\"\""))
+    } else {
+        v.to_string()
+    }
+}
+
+fn to_csv(events: &[serde_json::Value], start_ms: u64) -> String {
+    let mut out = String::from(
+        "time,offset,type,connectionId,roomId,user,uid,text,giftName,giftNum,price,guardLevel,isModerator,isStreamer,hasOwnMedal\n",
+    );
+    for e in events {
+        let ts = event_time_ms(e);
+        let time = {
+            use chrono::TimeZone;
+            chrono::Local
+                .timestamp_millis_opt(ts as i64)
+                .single()
+                .map(|dt| dt.format("%Y-%m-%d %H:%M:%S%.3f").to_string())
+                .unwrap_or_default()
+        };
+        let offset = if ts >= start_ms {
+            srt_time(ts - start_ms).replace(',', ".")
+        } else {
+            String::new()
+        };
+        let num = |key: &str| e.get(key).map(|v| v.to_string()).filter(|s| s != "null").unwrap_or_default();
+        let flag = |key: &str| e.get(key).and_then(|v| v.as_bool()).unwrap_or(false).to_string();
+        let row = [
+            time,
+            offset,
+            event_kind(e).to_string(),
+            str_field(e, "connectionId").to_string(),
+            num("roomId"),
+            str_field(e, "user").to_string(),
+            num("uid"),
+            str_field(e, "text").to_string(),
+            str_field(e, "giftName").to_string(),
+            e.get("giftNum").or_else(|| e.get("num")).map(|v| v.to_string()).unwrap_or_default(),
+            num("price"),
+            num("guardLevel"),
+            flag("isModerator"),
+            flag("isStreamer"),
+            flag("hasOwnMedal"),
+        ];
+        out.push_str(&row.iter().map(|v| csv_escape(v)).collect::<Vec<_>>().join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn srt_time(ms: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+// 看过人数等统计类事件不适合做成字幕
+fn is_caption_event(e: &serde_json::Value) -> bool {
+    !matches!(event_kind(e), "watched")
+}
+
+fn to_srt(events: &[serde_json::Value], start_ms: u64) -> String {
+    let mut out = String::new();
+    let mut index = 0;
+    for e in events.iter().filter(|e| is_caption_event(e)) {
+        let ts = event_time_ms(e);
+        if ts < start_ms {
+            continue;
+        }
+        index += 1;
+        let begin = ts - start_ms;
+        let end = begin + event_duration_ms(e);
+        out.push_str(&format!(
+            "{index}\n{} --> {}\n{}\n\n",
+            srt_time(begin),
+            srt_time(end),
+            caption_text(e).replace("\r\n", "\n")
+        ));
+    }
+    out
+}
+
+/* ---------------- ASS ---------------- */
+
+// ASS 样式名与 StyleProfile 字段对应
+const ASS_STYLE_BASE: &str = "Base";
+const ASS_STYLE_OWN_MEDAL: &str = "OwnMedal";
+const ASS_STYLE_GUARD_GOVERNOR: &str = "GuardGovernor";
+const ASS_STYLE_GUARD_ADMIRAL: &str = "GuardAdmiral";
+const ASS_STYLE_GUARD_CAPTAIN: &str = "GuardCaptain";
+const ASS_STYLE_STREAMER: &str = "Streamer";
+const ASS_STYLE_MODERATOR: &str = "Moderator";
+
+fn ass_time(ms: u64) -> String {
+    let cs = ms / 10;
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        cs / 360_000,
+        cs / 6000 % 60,
+        cs / 100 % 60,
+        cs % 100
+    )
+}
+
+/// "#rrggbb" -> "&H00BBGGRR"（ASS 颜色是 BGR 顺序，前两位为透明度）
+fn ass_color(hex: &str) -> Option<String> {
+    let hex = hex.trim().trim_start_matches('#');
+    let hex = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect::<String>(),
+        6 => hex.to_string(),
+        _ => return None,
+    };
+    let rgb = u32::from_str_radix(&hex, 16).ok()?;
+    Some(format!(
+        "&H00{:02X}{:02X}{:02X}",
+        rgb & 0xff,
+        (rgb >> 8) & 0xff,
+        (rgb >> 16) & 0xff
+    ))
+}
+
+fn ass_style_line(name: &str, cfg: &Config) -> String {
+    // color 为 null 时使用弹幕原始颜色：样式里给白色，Dialogue 行再用 \c 覆盖
+    let primary = cfg.color.as_deref().and_then(ass_color).unwrap_or_else(|| "&H00FFFFFF".to_string());
+    let outline = cfg.stroke_color.as_deref().and_then(ass_color).unwrap_or_else(|| "&H00000000".to_string());
+    format!(
+        "Style: {name},Microsoft YaHei,{},{primary},{primary},{outline},&H00000000,0,0,0,0,100,100,0,0,1,{},0,2,20,20,40,1\n",
+        cfg.font_size, cfg.stroke_width
+    )
+}
+
+/// 与 apply_style_to_sse_message 的合成顺序一致，取最终生效的那一层作为 Dialogue 的样式名
+fn ass_style_name(e: &serde_json::Value, profile: &StyleProfile) -> String {
+    let kind = event_kind(e);
+    if kind == "danmu" {
+        let flag = |key: &str| e.get(key).and_then(|v| v.as_bool()).unwrap_or(false);
+        if flag("isModerator") && profile.moderator.is_some() {
+            return ASS_STYLE_MODERATOR.to_string();
+        }
+        if flag("isStreamer") && profile.streamer.is_some() {
+            return ASS_STYLE_STREAMER.to_string();
+        }
+        let guard = match e.get("guardLevel").and_then(|v| v.as_u64()) {
+            Some(1) if profile.guard_governor.is_some() => Some(ASS_STYLE_GUARD_GOVERNOR),
+            Some(2) if profile.guard_admiral.is_some() => Some(ASS_STYLE_GUARD_ADMIRAL),
+            Some(3) if profile.guard_captain.is_some() => Some(ASS_STYLE_GUARD_CAPTAIN),
+            _ => None,
+        };
+        if let Some(guard) = guard {
+            return guard.to_string();
+        }
+        if flag("hasOwnMedal") && profile.own_medal.is_some() {
+            return ASS_STYLE_OWN_MEDAL.to_string();
+        }
+    }
+    // 其它类型没有单独样式时继承 danmu
+    for key in [kind, "danmu"] {
+        if profile.by_type.contains_key(key) {
+            return format!("Type_{key}");
+        }
+    }
+    ASS_STYLE_BASE.to_string()
+}
+
+/// 花括号会被当成覆盖标签，换成全角；换行用 \N
+fn ass_escape(text: &str) -> String {
+    text.replace('{', "｛")
+        .replace('}', "｝")
+        .replace("\r\n", "\\N")
+        .replace('\n', "\\N")
+}
+
+fn to_ass(events: &[serde_json::Value], start_ms: u64, profile: &StyleProfile) -> String {
+    let mut out = String::from(
+        "[Script Info]\nScriptType: v4.00+\nPlayResX: 1920\nPlayResY: 1080\nWrapStyle: 0\nScaledBorderAndShadow: yes\n\n",
+    );
+
+    out.push_str("[V4+ Styles]\nFormat: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n");
+    out.push_str(&ass_style_line(ASS_STYLE_BASE, &profile.base));
+    let mut by_type = profile.by_type.iter().collect::<Vec<_>>();
+    by_type.sort_by(|a, b| a.0.cmp(b.0));
+    for (kind, cfg) in by_type {
+        out.push_str(&ass_style_line(&format!("Type_{kind}"), cfg));
+    }
+    // 高亮层只覆盖视觉字段，样式里其余字段沿用 danmu 的有效样式
+    let danmu = profile.by_type.get("danmu").unwrap_or(&profile.base);
+    for (name, overlay) in [
+        (ASS_STYLE_OWN_MEDAL, &profile.own_medal),
+        (ASS_STYLE_GUARD_GOVERNOR, &profile.guard_governor),
+        (ASS_STYLE_GUARD_ADMIRAL, &profile.guard_admiral),
+        (ASS_STYLE_GUARD_CAPTAIN, &profile.guard_captain),
+        (ASS_STYLE_STREAMER, &profile.streamer),
+        (ASS_STYLE_MODERATOR, &profile.moderator),
+    ] {
+        if let Some(overlay) = overlay {
+            let cfg = Config {
+                font_size: overlay.font_size,
+                color: overlay.color.clone().or_else(|| danmu.color.clone()),
+                stroke_color: overlay.stroke_color.clone().or_else(|| danmu.stroke_color.clone()),
+                stroke_width: overlay.stroke_width,
+                ..danmu.clone()
+            };
+            out.push_str(&ass_style_line(name, &cfg));
+        }
+    }
+
+    out.push_str("\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n");
+    for e in events.iter().filter(|e| is_caption_event(e)) {
+        let ts = event_time_ms(e);
+        if ts < start_ms {
+            continue;
+        }
+        let begin = ts - start_ms;
+        let end = begin + event_duration_ms(e);
+        let fade = e.get("fadeDuration").and_then(|v| v.as_u64()).unwrap_or(1000);
+        // 每条实际推送的颜色已写在事件里（含 color 为 null 时回退的弹幕原色），逐行覆盖保证与直播画面一致
+        // 覆盖标签里的颜色不带透明度：\c&HBBGGRR&
+        let color = ass_color(str_field(e, "color"))
+            .map(|c| format!("\\c&H{}&", &c[4..]))
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "Dialogue: 0,{},{},{},{},0,0,0,,{{\\fad(0,{fade}){color}}}{}\n",
+            ass_time(begin),
+            ass_time(end),
+            ass_style_name(e, profile),
+            ass_escape(str_field(e, "user")).replace(',', "，"),
+            ass_escape(&caption_text(e)),
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn writer_thread_flushes_on_request_and_at_session_end() {
+        let dir = std::env::temp_dir().join(format!("transcript-test-{}", crate::live_event::new_event_id()));
+        let session = start_session(&dir).unwrap();
+        let path = session.path.clone();
+
+        session.writer.send(WriterCommand::Line(r#"{"type":"danmu"}"#.to_string())).unwrap();
+        let (done, flushed) = oneshot::channel();
+        session.writer.send(WriterCommand::Flush(done)).unwrap();
+        flushed.await.unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+
+        session.writer.send(WriterCommand::Line(r#"{"type":"gift"}"#.to_string())).unwrap();
+        drop(session);
+        // 发送端丢弃后写线程落盘退出
+        let mut lines = 0;
+        for _ in 0..50 {
+            lines = fs::read_to_string(&path).unwrap().lines().count();
+            if lines == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(lines, 2);
+    }
+}
@@ -0,0 +1,500 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use futures::future::BoxFuture;
+use futures_util::{stream::StreamExt, SinkExt};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+use crate::bili_websocket_client::{gift_text, heartbeat_timeout_secs, random_u64, reconnect_backoff};
+use crate::live_event::{new_event_id, DanmuEvent, EventCommon, GiftEvent, LiveEvent};
+use crate::live_source::{LiveSource, SourceContext};
+
+// Twitch 聊天室的 IRC-over-WebSocket 入口；本地调试可用 TWITCH_IRC_WS_URL 指向自建的 IRC 替身
+const TWITCH_IRC_WS: &str = "wss://irc-ws.chat.twitch.tv:443";
+// Twitch 服务端约 5 分钟才 PING 一次，主动 PING 让看门狗能及时发现僵死连接
+const TWITCH_PING_INTERVAL: Duration = Duration::from_secs(20);
+
+fn twitch_irc_url() -> String {
+    std::env::var("TWITCH_IRC_WS_URL")
+        .ok()
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or_else(|| TWITCH_IRC_WS.to_string())
+}
+
+/// Twitch 聊天（只读）：以 justinfanXXXX 匿名登录，无需 OAuth
+pub struct TwitchChatSource {
+    channel: String,
+    reconnect_interval: u64,
+    max_reconnect_attempts: u32,
+}
+
+impl TwitchChatSource {
+    pub fn new(channel: &str, reconnect_interval: u64, max_reconnect_attempts: u32) -> Self {
+        // 频道名不区分大小写，IRC 里统一小写且不带 #
+        let channel = channel.trim().trim_start_matches('#').to_lowercase();
+        Self {
+            channel,
+            reconnect_interval,
+            max_reconnect_attempts,
+        }
+    }
+}
+
+impl LiveSource for TwitchChatSource {
+    fn platform(&self) -> &'static str {
+        "twitch"
+    }
+
+    fn connect(self: Arc<Self>, ctx: SourceContext) -> BoxFuture<'static, ()> {
+        Box::pin(async move { run_irc_loop(ctx, &self.channel, self.reconnect_interval, self.max_reconnect_attempts).await })
+    }
+}
+
+async fn run_irc_loop(ctx: SourceContext, channel: &str, reconnect_interval: u64, max_reconnect_attempts: u32) {
+    let connection_id = ctx.connection_id().to_string();
+    if channel.is_empty() {
+        ctx.status("error", None, "Twitch 频道名为空").await;
+        return;
+    }
+
+    let mut attempts = 0;
+    while attempts <= max_reconnect_attempts {
+        // 重连前确认连接没有被断开
+        if attempts > 0 && ctx.should_stop().await {
+            break;
+        }
+
+        match connect_async(twitch_irc_url()).await {
+            Ok((ws_stream, _resp)) => {
+                let (mut write, mut read) = ws_stream.split();
+
+                // 匿名登录：PASS 任意，NICK 为 justinfan + 数字；tags 能力用于拿到颜色/徽章/bits
+                let nick = format!("justinfan{}", 10000 + random_u64() % 90000);
+                let login = [
+                    "CAP REQ :twitch.tv/tags twitch.tv/commands".to_string(),
+                    "PASS SCHMOOPIIE".to_string(),
+                    format!("NICK {nick}"),
+                    format!("JOIN #{channel}"),
+                ];
+                let mut login_failed = false;
+                for line in login {
+                    if let Err(e) = write.send(Message::Text(line.into())).await {
+                        eprintln!("[Twitch][{connection_id}] 登录失败: {e}");
+                        login_failed = true;
+                        break;
+                    }
+                }
+
+                if !login_failed {
+                    let mut ping = tokio::time::interval(TWITCH_PING_INTERVAL);
+                    let mut watchdog = tokio::time::interval(Duration::from_secs(5));
+                    let mut last_seen = tokio::time::Instant::now();
+
+                    loop {
+                        tokio::select! {
+                            _ = watchdog.tick() => {
+                                let timeout_secs = heartbeat_timeout_secs();
+                                if timeout_secs > 0 && last_seen.elapsed() > Duration::from_secs(timeout_secs) {
+                                    eprintln!("[Twitch][{connection_id}] {timeout_secs}s 未收到服务端数据，判定连接僵死，准备重连");
+                                    ctx.status("stale", None, &format!("{}s 未收到心跳回复，正在重连", timeout_secs)).await;
+                                    break;
+                                }
+                            }
+                            _ = ping.tick() => {
+                                if let Err(e) = write.send(Message::Text("PING :tmi.twitch.tv".into())).await {
+                                    eprintln!("[Twitch][{connection_id}] 心跳失败: {e}");
+                                    ctx.status("disconnected", None, "心跳失败").await;
+                                    break;
+                                }
+                            }
+                            msg = read.next() => {
+                                if matches!(msg, Some(Ok(_))) {
+                                    last_seen = tokio::time::Instant::now();
+                                }
+                                match msg {
+                                    Some(Ok(Message::Text(txt))) => {
+                                        let mut reconnect = false;
+                                        // 一个帧里可能有多行，以 \r\n 分隔
+                                        for line in txt.split("\r\n").filter(|l| !l.is_empty()) {
+                                            let Some(irc) = parse_irc_line(line) else {
+                                                continue;
+                                            };
+                                            match irc.command {
+                                                "PING" => {
+                                                    let pong = format!("PONG :{}", irc.trailing.unwrap_or("tmi.twitch.tv"));
+                                                    let _ = write.send(Message::Text(pong.into())).await;
+                                                }
+                                                // 服务端回显自己的 JOIN 即加入成功
+                                                "JOIN" if irc.nick == Some(nick.as_str()) => {
+                                                    println!("[Twitch][{connection_id}] 已加入 #{channel}");
+                                                    ctx.status("connected", None, "连接成功").await;
+                                                    attempts = 0;
+                                                }
+                                                // 频道 uid 作为房间号与主播 uid：annotate_event 据此给主播本人的消息打 isStreamer
+                                                "ROOMSTATE" => {
+                                                    let room_id = irc.tags.get("room-id").and_then(|v| v.parse::<i64>().ok());
+                                                    if room_id.is_some() {
+                                                        ctx.set_room(room_id, room_id, None).await;
+                                                    }
+                                                }
+                                                "PRIVMSG" => {
+                                                    for event in parse_privmsg(&irc) {
+                                                        ctx.emit(event).await;
+                                                    }
+                                                }
+                                                "NOTICE" => {
+                                                    eprintln!("[Twitch][{connection_id}] NOTICE: {}", irc.trailing.unwrap_or(""));
+                                                }
+                                                // 服务端即将重启，要求客户端重连
+                                                "RECONNECT" => {
+                                                    reconnect = true;
+                                                }
+                                                _ => {}
+                                            }
+                                        }
+                                        if reconnect {
+                                            ctx.status("disconnected", None, "服务端要求重连").await;
+                                            break;
+                                        }
+                                    }
+                                    Some(Ok(Message::Close(frame))) => {
+                                        eprintln!("[Twitch][{connection_id}] 服务端关闭: {:?}", frame);
+                                        ctx.status("disconnected", None, "服务端关闭").await;
+                                        break;
+                                    }
+                                    Some(Ok(_)) => {}
+                                    Some(Err(e)) => {
+                                        eprintln!("[Twitch][{connection_id}] 读取错误: {e}");
+                                        ctx.status("disconnected", None, &format!("读取错误: {}", e)).await;
+                                        break;
+                                    }
+                                    std::option::Option::None => {
+                                        println!("[Twitch][{connection_id}] 连接结束");
+                                        ctx.status("disconnected", None, "连接结束").await;
+                                        break;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("[Twitch][{connection_id}] 连接失败: {e}");
+            }
+        }
+
+        attempts += 1;
+        if attempts > max_reconnect_attempts {
+            eprintln!("[Twitch][{connection_id}] 达到最大重连次数，停止");
+            ctx.status("error", None, "达到最大重连次数，已停止").await;
+            break;
+        }
+        ctx.status("reconnecting", None, &format!("重连中 {}/{}", attempts, max_reconnect_attempts)).await;
+        let delay = reconnect_backoff(reconnect_interval, attempts);
+        println!("[Twitch][{connection_id}] {}ms 后重连 {attempts}/{max_reconnect_attempts} …", delay.as_millis());
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// 一行 IRC 消息：@tags :prefix COMMAND params :trailing
+struct IrcLine<'a> {
+    tags: HashMap<&'a str, String>,
+    // prefix 中的昵称（nick!user@host 的 nick）
+    nick: Option<&'a str>,
+    command: &'a str,
+    trailing: Option<&'a str>,
+}
+
+fn parse_irc_line(line: &str) -> Option<IrcLine<'_>> {
+    let mut rest = line;
+
+    let mut tags = HashMap::new();
+    if let Some(stripped) = rest.strip_prefix('@') {
+        let (raw_tags, after) = stripped.split_once(' ')?;
+        for tag in raw_tags.split(';') {
+            let (k, v) = tag.split_once('=').unwrap_or((tag, ""));
+            tags.insert(k, unescape_tag_value(v));
+        }
+        rest = after;
+    }
+
+    let mut nick = None;
+    if let Some(stripped) = rest.strip_prefix(':') {
+        let (prefix, after) = stripped.split_once(' ')?;
+        nick = prefix.split('!').next().filter(|s| !s.is_empty());
+        rest = after;
+    }
+
+    let (head, trailing) = match rest.split_once(" :") {
+        Some((head, trailing)) => (head, Some(trailing)),
+        std::option::Option::None => (rest, None),
+    };
+    let command = head.split(' ').next().filter(|s| !s.is_empty())?;
+
+    Some(IrcLine {
+        tags,
+        nick,
+        command,
+        trailing,
+    })
+}
+
+/// IRCv3 tag 值转义：\s 空格、\: 分号、\\ 反斜杠、\r \n
+fn unescape_tag_value(v: &str) -> String {
+    let mut out = String::with_capacity(v.len());
+    let mut chars = v.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => out.push(' '),
+            Some(':') => out.push(';'),
+            Some('\\') => out.push('\\'),
+            Some('r') => out.push('\r'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            std::option::Option::None => {}
+        }
+    }
+    out
+}
+
+/// PRIVMSG -> 弹幕；带 bits 的 Cheer 照常作为弹幕显示留言，另外附带一条礼物事件记录 bits。
+/// 徽章映射到与 B 站相同的字段，StyleProfile 的主播/房管/粉丝牌高亮无需改动：
+/// broadcaster -> isStreamer，moderator -> isModerator，subscriber/founder -> hasOwnMedal（等级为订阅月数）
+fn parse_privmsg(irc: &IrcLine<'_>) -> Vec<LiveEvent> {
+    let Some(trailing) = irc.trailing else {
+        return Vec::new();
+    };
+    let mut text = trailing.to_string();
+    // /me 动作消息：\x01ACTION ...\x01
+    if let Some(action) = text.strip_prefix("\u{1}ACTION ") {
+        text = action.trim_end_matches('\u{1}').to_string();
+    }
+
+    let user = irc
+        .tags
+        .get("display-name")
+        .filter(|s| !s.is_empty())
+        .cloned()
+        .or_else(|| irc.nick.map(|s| s.to_string()));
+    let time_ms = irc.tags.get("tmi-sent-ts").and_then(|v| v.parse::<u64>().ok());
+
+    let mut common = EventCommon::new(text, user, time_ms);
+    common.user.uid = irc.tags.get("user-id").and_then(|v| v.parse::<u64>().ok());
+
+    // 未设置颜色的用户 color 为空，保持默认白色
+    if let Some(color) = irc.tags.get("color").filter(|s| s.starts_with('#')) {
+        common.color = color.to_lowercase();
+    }
+
+    // badges=broadcaster/1,subscriber/12；badge-info=subscriber/14 为实际订阅月数
+    let badges: HashMap<&str, &str> = irc
+        .tags
+        .get("badges")
+        .map(|s| s.split(',').filter_map(|b| b.split_once('/')).collect())
+        .unwrap_or_default();
+    let badge_info: HashMap<&str, &str> = irc
+        .tags
+        .get("badge-info")
+        .map(|s| s.split(',').filter_map(|b| b.split_once('/')).collect())
+        .unwrap_or_default();
+
+    common.user.is_streamer = badges.contains_key("broadcaster");
+    common.user.is_moderator = badges.contains_key("moderator") || irc.tags.get("mod").map(|v| v == "1").unwrap_or(false);
+    // founder 是前若干位订阅者的专属徽章，会替换 subscriber 徽章
+    if let Some(badge) = ["subscriber", "founder"].into_iter().find(|b| badges.contains_key(b)) {
+        common.medal.has_own_medal = true;
+        common.medal.medal_name = Some(badge.to_string());
+        common.medal.medal_level = badge_info
+            .get(badge)
+            .or_else(|| badge_info.get("subscriber"))
+            .and_then(|v| v.parse::<u32>().ok());
+    }
+
+    let bits = irc.tags.get("bits").and_then(|v| v.parse::<u64>().ok()).unwrap_or(0);
+    let gift = (bits > 0).then(|| {
+        let mut common = common.clone();
+        common.id = new_event_id();
+        common.text = gift_text("Bits", bits);
+        // 1 bit 约合 0.01 美元，这里 price 记为 1，totalCoin 即 bits 数
+        LiveEvent::Gift(GiftEvent {
+            common,
+            gift_name: "Bits".to_string(),
+            gift_num: bits,
+            coin_type: "bits".to_string(),
+            price: 1,
+            total_coin: bits,
+        })
+    });
+
+    let mut events = vec![LiveEvent::Danmu(DanmuEvent { common })];
+    events.extend(gift);
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn privmsg(line: &str) -> LiveEvent {
+        privmsg_events(line).into_iter().next().expect("event")
+    }
+
+    fn privmsg_events(line: &str) -> Vec<LiveEvent> {
+        let irc = parse_irc_line(line).expect("irc line");
+        assert_eq!(irc.command, "PRIVMSG");
+        parse_privmsg(&irc)
+    }
+
+    #[test]
+    fn parses_tags_prefix_and_trailing() {
+        let irc = parse_irc_line(
+            "@badge-info=;color=#1E90FF;display-name=Foo;system-msg=hello\\sworld\\:\\\\x :foo!foo@foo.tmi.twitch.tv PRIVMSG #bar :hi :)",
+        )
+        .unwrap();
+        assert_eq!(irc.nick, Some("foo"));
+        assert_eq!(irc.command, "PRIVMSG");
+        assert_eq!(irc.trailing, Some("hi :)"));
+        assert_eq!(irc.tags.get("display-name").map(String::as_str), Some("Foo"));
+        assert_eq!(irc.tags.get("badge-info").map(String::as_str), Some(""));
+        assert_eq!(irc.tags.get("system-msg").map(String::as_str), Some("hello world;\\x"));
+
+        let ping = parse_irc_line("PING :tmi.twitch.tv").unwrap();
+        assert_eq!(ping.command, "PING");
+        assert_eq!(ping.nick, None);
+        assert_eq!(ping.trailing, Some("tmi.twitch.tv"));
+    }
+
+    #[test]
+    fn unescapes_tag_values() {
+        assert_eq!(unescape_tag_value("a\\sb\\:c\\\\d\\r\\n"), "a b;c\\d\r\n");
+        // 未知转义保留字符本身，末尾孤立的反斜杠丢弃
+        assert_eq!(unescape_tag_value("\\x\\"), "x");
+    }
+
+    #[test]
+    fn parses_plain_and_action_messages() {
+        let event = privmsg("@color=#FF0000;display-name=Viewer;user-id=42;tmi-sent-ts=1700000000000 :viewer!viewer@viewer.tmi.twitch.tv PRIVMSG #chan :hello there");
+        let LiveEvent::Danmu(danmu) = &event else {
+            panic!("expected danmu");
+        };
+        assert_eq!(danmu.common.text, "hello there");
+        assert_eq!(danmu.common.user.name, "Viewer");
+        assert_eq!(danmu.common.user.uid, Some(42));
+        assert_eq!(danmu.common.color, "#ff0000");
+        assert_eq!(danmu.common.timestamp, 1_700_000_000_000);
+
+        // /me 动作消息去掉 CTCP 包装；没有 display-name 时用 prefix 昵称
+        let event = privmsg(":viewer!viewer@viewer.tmi.twitch.tv PRIVMSG #chan :\u{1}ACTION waves\u{1}");
+        assert_eq!(event.common().text, "waves");
+        assert_eq!(event.common().user.name, "viewer");
+    }
+
+    #[test]
+    fn maps_badges() {
+        let event = privmsg("@badges=broadcaster/1,subscriber/12;badge-info=subscriber/14 :chan!chan@chan.tmi.twitch.tv PRIVMSG #chan :hi");
+        let common = event.common();
+        assert!(common.user.is_streamer);
+        assert!(!common.user.is_moderator);
+        assert!(common.medal.has_own_medal);
+        assert_eq!(common.medal.medal_name.as_deref(), Some("subscriber"));
+        assert_eq!(common.medal.medal_level, Some(14));
+
+        let event = privmsg("@badges=moderator/1,founder/0;badge-info=founder/3 :m!m@m.tmi.twitch.tv PRIVMSG #chan :hi");
+        let common = event.common();
+        assert!(!common.user.is_streamer);
+        assert!(common.user.is_moderator);
+        assert_eq!(common.medal.medal_name.as_deref(), Some("founder"));
+        assert_eq!(common.medal.medal_level, Some(3));
+
+        // 没有 moderator 徽章但 mod=1（例如同时佩戴其它徽章）
+        let event = privmsg("@badges=;mod=1 :m!m@m.tmi.twitch.tv PRIVMSG #chan :hi");
+        assert!(event.common().user.is_moderator);
+        assert!(!event.common().medal.has_own_medal);
+    }
+
+    #[test]
+    fn cheer_keeps_message_and_adds_bits_gift() {
+        let events = privmsg_events("@bits=100;display-name=Cheerer;user-id=7 :c!c@c.tmi.twitch.tv PRIVMSG #chan :Cheer100 gg");
+        let [LiveEvent::Danmu(danmu), LiveEvent::Gift(gift)] = &events[..] else {
+            panic!("expected danmu + gift, got {events:?}");
+        };
+        assert_eq!(danmu.common.text, "Cheer100 gg");
+        assert_eq!(gift.gift_name, "Bits");
+        assert_eq!(gift.coin_type, "bits");
+        assert_eq!(gift.gift_num, 100);
+        assert_eq!(gift.total_coin, 100);
+        assert_eq!(gift.common.user.uid, Some(7));
+        assert_ne!(gift.common.id, danmu.common.id);
+
+        assert_eq!(privmsg_events("@bits=0 :c!c@c.tmi.twitch.tv PRIVMSG #chan :hi").len(), 1);
+    }
+
+    // 替身收到的下一行（跳过客户端自己的保活 PING）
+    async fn next_line<S>(ws: &mut tokio_tungstenite::WebSocketStream<S>) -> String
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        loop {
+            match ws.next().await {
+                Some(Ok(Message::Text(txt))) if !txt.starts_with("PING") => return txt.to_string(),
+                Some(Ok(_)) => continue,
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+    }
+
+    // 本地 IRC-over-WebSocket 替身：检查匿名登录、JOIN、PING/PONG，以及 PRIVMSG 解析后送入事件管线
+    #[tokio::test]
+    async fn runs_against_local_irc_server() {
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        std::env::set_var("TWITCH_IRC_WS_URL", format!("ws://{}", listener.local_addr().unwrap()));
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            assert!(next_line(&mut ws).await.starts_with("CAP REQ"));
+            assert!(next_line(&mut ws).await.starts_with("PASS "));
+            let nick = next_line(&mut ws).await.strip_prefix("NICK ").unwrap().to_string();
+            assert!(nick.starts_with("justinfan"));
+            assert_eq!(next_line(&mut ws).await, "JOIN #chan");
+
+            let send = |line: String| Message::Text(line.into());
+            ws.send(send(format!(":{nick}!{nick}@{nick}.tmi.twitch.tv JOIN #chan"))).await.unwrap();
+            ws.send(send("PING :tmi.twitch.tv".to_string())).await.unwrap();
+            assert_eq!(next_line(&mut ws).await, "PONG :tmi.twitch.tv");
+            let frame = [
+                "@display-name=Viewer;user-id=42 :viewer!viewer@viewer.tmi.twitch.tv PRIVMSG #chan :hello",
+                "@bits=50;display-name=Cheerer;user-id=43 :c!c@c.tmi.twitch.tv PRIVMSG #chan :Cheer50 gg",
+            ]
+            .join("\r\n");
+            ws.send(send(frame)).await.unwrap();
+            ws.close(None).await.unwrap();
+        });
+
+        // 直接跑 IRC 循环，从来源上下文的通道读出送往管线的事件；不重连，服务端关闭后结束
+        let (ctx, mut events) = SourceContext::new(format!("twitch-test-{}", new_event_id()));
+        let client = tokio::spawn(run_irc_loop(ctx, "chan", 100, 0));
+        let mut received = Vec::new();
+        while let Some(event) = tokio::time::timeout(Duration::from_secs(10), events.recv()).await.expect("irc loop finished") {
+            received.push(event);
+        }
+        client.await.unwrap();
+        server.await.unwrap();
+
+        // 两条弹幕 + 一条 bits 礼物
+        let summary = received
+            .iter()
+            .map(|e| (e.kind(), e.common().user.name.as_str(), e.common().text.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [("danmu", "Viewer", "hello"), ("danmu", "Cheerer", "Cheer50 gg"), ("gift", "Cheerer", "送出 Bits x50")]
+        );
+    }
+}